@@ -0,0 +1,312 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    thread,
+    time::Duration,
+};
+
+use crate::{grid::Grid, Options};
+
+/// The colors a terminal can draw with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+impl Color {
+    fn ansi_code(self) -> u8 {
+        30 + self as u8
+    }
+}
+
+/// How a grid cell is drawn in a terminal.
+pub trait Sprite {
+    fn symbol(&self) -> char;
+
+    fn color(&self) -> Option<Color> {
+        None
+    }
+}
+
+impl Sprite for char {
+    fn symbol(&self) -> char {
+        *self
+    }
+}
+
+impl Sprite for bool {
+    fn symbol(&self) -> char {
+        if *self {
+            '#'
+        } else {
+            '.'
+        }
+    }
+}
+
+/// Cells that are not there are drawn as `.`.
+impl<T: Sprite> Sprite for Option<T> {
+    fn symbol(&self) -> char {
+        self.as_ref().map_or('.', |s| s.symbol())
+    }
+
+    fn color(&self) -> Option<Color> {
+        self.as_ref().and_then(|s| s.color())
+    }
+}
+
+/// How the frames of an animation are shown.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    /// Redraw every frame in place, waiting `delay` between frames.
+    Interactive { delay: Duration, color: bool },
+    /// Write every frame after the previous one, each with a header.
+    Dump,
+}
+
+/// Draws the generations of a grid simulation one frame at a time.
+///
+/// # Examples
+///
+/// ```
+/// use utils::animate::{Animation, Mode};
+/// use utils::grid::Grid;
+///
+/// let mut out = vec![];
+/// let mut animation = Animation::new(&mut out, Mode::Dump);
+/// animation.frame(0, &Grid::parse("#.\n.#", |c| Some(c == '#')).unwrap()).unwrap();
+/// animation.frame(1, &Grid::parse("..\n..", |c| Some(c == '#')).unwrap()).unwrap();
+/// drop(animation);
+/// assert_eq!(
+///     String::from_utf8(out).unwrap(),
+///     "Generation 0\n#.\n.#\n\nGeneration 1\n..\n..\n\n"
+/// );
+/// ```
+///
+/// Interactive animations clear the screen before the first frame, hide
+/// the cursor and move it back up over the previous frame before drawing
+/// each frame. The cursor is shown again by [`Animation::finish`], or when
+/// the animation is dropped without finishing, e.g. because drawing a
+/// frame failed:
+///
+/// ```
+/// use std::time::Duration;
+/// use utils::animate::{Animation, Color, Mode, Sprite};
+/// use utils::grid::Grid;
+///
+/// struct Tree;
+///
+/// impl Sprite for Tree {
+///     fn symbol(&self) -> char {
+///         '#'
+///     }
+///
+///     fn color(&self) -> Option<Color> {
+///         Some(Color::Green)
+///     }
+/// }
+///
+/// let mut out = vec![];
+/// let mode = Mode::Interactive { delay: Duration::from_millis(0), color: true };
+/// let mut animation = Animation::new(&mut out, mode);
+/// animation.frame(0, &Grid::from_fn(1, 2, |_| Tree)).unwrap();
+/// animation.finish().unwrap();
+/// drop(animation);
+/// assert_eq!(
+///     String::from_utf8(out).unwrap(),
+///     "\x1b[2J\x1b[H\x1b[?25lGeneration 0\n\x1b[32m##\x1b[0m\n\x1b[?25h"
+/// );
+///
+/// let mut out = vec![];
+/// let mut animation = Animation::new(&mut out, mode);
+/// animation.frame(0, &Grid::parse("#", |_| Some(false)).unwrap()).unwrap();
+/// drop(animation);
+/// assert!(String::from_utf8(out).unwrap().ends_with("\x1b[?25h"));
+/// ```
+pub struct Animation<W: Write> {
+    out: W,
+    mode: Mode,
+    cleared: bool,
+    /// The number of lines of the last frame drawn since the animation
+    /// started or was last finished.
+    lines: usize,
+}
+
+impl<W: Write> Animation<W> {
+    pub fn new(out: W, mode: Mode) -> Animation<W> {
+        Animation {
+            out,
+            mode,
+            cleared: false,
+            lines: 0,
+        }
+    }
+
+    /// Write a title above the frames drawn from now on, e.g. to tell
+    /// apart the parts of a puzzle drawn by the same animation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use utils::animate::{Animation, Mode};
+    /// use utils::grid::Grid;
+    ///
+    /// let grid = Grid::parse("#", |c| Some(c == '#')).unwrap();
+    /// let mut out = vec![];
+    /// let mut animation = Animation::new(&mut out, Mode::Dump);
+    /// for part in &["Part 1", "Part 2"] {
+    ///     animation.section(part).unwrap();
+    ///     animation.frame(0, &grid).unwrap();
+    ///     animation.finish().unwrap();
+    /// }
+    /// drop(animation);
+    /// assert_eq!(
+    ///     String::from_utf8(out).unwrap(),
+    ///     "== Part 1 ==\n\nGeneration 0\n#\n\n== Part 2 ==\n\nGeneration 0\n#\n\n"
+    /// );
+    ///
+    /// // The screen is only cleared once, so that whatever was written
+    /// // after the first part stays above the second one.
+    /// let mut out = vec![];
+    /// let mode = Mode::Interactive { delay: Duration::from_millis(0), color: false };
+    /// let mut animation = Animation::new(&mut out, mode);
+    /// animation.section("Part 1").unwrap();
+    /// animation.frame(0, &grid).unwrap();
+    /// animation.frame(1, &grid).unwrap();
+    /// animation.finish().unwrap();
+    /// animation.section("Part 2").unwrap();
+    /// animation.frame(0, &grid).unwrap();
+    /// drop(animation);
+    /// assert_eq!(
+    ///     String::from_utf8(out).unwrap(),
+    ///     "\x1b[2J\x1b[H== Part 1 ==\n\x1b[?25lGeneration 0\n#\n\x1b[2FGeneration 1\n#\n\x1b[?25h\
+    ///      == Part 2 ==\n\x1b[?25lGeneration 0\n#\n\x1b[?25h"
+    /// );
+    /// ```
+    pub fn section(&mut self, title: &str) -> io::Result<()> {
+        match self.mode {
+            Mode::Interactive { .. } => {
+                self.clear_once()?;
+                writeln!(self.out, "== {} ==", title)?;
+            }
+            Mode::Dump => writeln!(self.out, "== {} ==\n", title)?,
+        }
+        Ok(())
+    }
+
+    /// Draw the grid of a generation.
+    pub fn frame<T: Sprite>(&mut self, generation: usize, grid: &Grid<T>) -> io::Result<()> {
+        match self.mode {
+            Mode::Interactive { delay, color } => {
+                self.clear_once()?;
+                if self.lines == 0 {
+                    write!(self.out, "\x1b[?25l")?;
+                } else {
+                    // Move back up to the first line of the previous frame.
+                    write!(self.out, "\x1b[{}F", self.lines)?;
+                }
+                writeln!(self.out, "Generation {}", generation)?;
+                self.draw(grid, color)?;
+                self.out.flush()?;
+                thread::sleep(delay);
+            }
+            Mode::Dump => {
+                writeln!(self.out, "Generation {}", generation)?;
+                self.draw(grid, false)?;
+                writeln!(self.out)?;
+            }
+        }
+        self.lines = grid.dimensions().0 + 1;
+        Ok(())
+    }
+
+    /// Restore the terminal after the last frame. Frames drawn afterwards
+    /// go below the last one instead of over it.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if let Mode::Interactive { .. } = self.mode {
+            if self.lines > 0 {
+                write!(self.out, "\x1b[?25h")?;
+            }
+        }
+        self.lines = 0;
+        self.out.flush()
+    }
+
+    /// Clear the screen and move to its top left corner, unless the
+    /// animation has already done so.
+    fn clear_once(&mut self) -> io::Result<()> {
+        if !self.cleared {
+            write!(self.out, "\x1b[2J\x1b[H")?;
+            self.cleared = true;
+        }
+        Ok(())
+    }
+
+    fn draw<T: Sprite>(&mut self, grid: &Grid<T>, color: bool) -> io::Result<()> {
+        for row in grid.rows() {
+            let mut current = None;
+            for cell in row {
+                if color && cell.color() != current {
+                    current = cell.color();
+                    match current {
+                        Some(c) => write!(self.out, "\x1b[{}m", c.ansi_code())?,
+                        None => write!(self.out, "\x1b[0m")?,
+                    }
+                }
+                write!(self.out, "{}", cell.symbol())?;
+            }
+            if current.is_some() {
+                write!(self.out, "\x1b[0m")?;
+            }
+            writeln!(self.out)?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Drop for Animation<W> {
+    /// Show the cursor again if the animation was not finished.
+    fn drop(&mut self) {
+        if let Mode::Interactive { .. } = self.mode {
+            if self.lines > 0 {
+                let _ = write!(self.out, "\x1b[?25h");
+                let _ = self.out.flush();
+            }
+        }
+    }
+}
+
+impl Animation<Box<dyn Write>> {
+    /// Set up an animation from the command line options, if one was
+    /// asked for.
+    ///
+    /// `--visualize` redraws the grid in the terminal every `--delay`
+    /// milliseconds (100 by default), in color if `--color` is given.
+    /// `--frames FILE` instead writes every frame to FILE.
+    pub fn from_options(options: &Options) -> Result<Option<Animation<Box<dyn Write>>>, String> {
+        if let Some(path) = options.get::<String>("frames")? {
+            let file =
+                File::create(&path).map_err(|e| format!("failed to create {}: {}", path, e))?;
+            return Ok(Some(Animation::new(
+                Box::new(BufWriter::new(file)),
+                Mode::Dump,
+            )));
+        }
+        if options.flag("visualize") {
+            let mode = Mode::Interactive {
+                delay: Duration::from_millis(options.get_or("delay", 100)?),
+                color: options.flag("color"),
+            };
+            return Ok(Some(Animation::new(Box::new(io::stdout()), mode)));
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,376 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    slice,
+};
+
+use crate::grid::{Grid, Visibility, MOORE, VON_NEUMANN};
+
+/// Decides which cells count as the neighbors of a cell.
+pub trait Neighborhood {
+    /// Push the positions of the neighbors of `position` onto `out`, in a
+    /// grid with the given number of rows and columns.
+    fn neighbors(
+        &self,
+        position: (usize, usize),
+        dimensions: (usize, usize),
+        out: &mut Vec<(usize, usize)>,
+    );
+}
+
+/// The cells at fixed offsets from a cell.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Offsets(pub Vec<(i64, i64)>);
+
+impl Offsets {
+    /// The eight cells surrounding a cell.
+    pub fn moore() -> Offsets {
+        Offsets(MOORE.to_vec())
+    }
+
+    /// The four cells sharing an edge with a cell.
+    pub fn von_neumann() -> Offsets {
+        Offsets(VON_NEUMANN.to_vec())
+    }
+
+    /// The cells at most `radius` steps away in any of the eight
+    /// directions, forming a square around the cell.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::automaton::Offsets;
+    ///
+    /// assert_eq!(Offsets::moore_radius(1).0.len(), Offsets::moore().0.len());
+    /// assert_eq!(Offsets::moore_radius(2).0.len(), 24);
+    /// ```
+    pub fn moore_radius(radius: usize) -> Offsets {
+        let r = radius as i64;
+        let offsets = (-r..=r)
+            .flat_map(|dr| (-r..=r).map(move |dc| (dr, dc)))
+            .filter(|o| *o != (0, 0))
+            .collect();
+        Offsets(offsets)
+    }
+
+    /// The cells at most `radius` steps away when only moving along the
+    /// axes, forming a diamond around the cell.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::automaton::Offsets;
+    ///
+    /// assert_eq!(Offsets::von_neumann_radius(1).0.len(), 4);
+    /// assert_eq!(Offsets::von_neumann_radius(2).0.len(), 12);
+    /// ```
+    pub fn von_neumann_radius(radius: usize) -> Offsets {
+        let r = radius as i64;
+        let offsets = (-r..=r)
+            .flat_map(|dr| (-r..=r).map(move |dc| (dr, dc)))
+            .filter(|(dr, dc)| (*dr, *dc) != (0, 0) && dr.abs() + dc.abs() <= r)
+            .collect();
+        Offsets(offsets)
+    }
+}
+
+impl Neighborhood for Offsets {
+    fn neighbors(
+        &self,
+        (row, column): (usize, usize),
+        (height, width): (usize, usize),
+        out: &mut Vec<(usize, usize)>,
+    ) {
+        for (dr, dc) in self.0.iter() {
+            let (r, c) = (row as i64 + dr, column as i64 + dc);
+            if r >= 0 && c >= 0 && (r as usize) < height && (c as usize) < width {
+                out.push((r as usize, c as usize));
+            }
+        }
+    }
+}
+
+/// The first cells visible in each direction, as precomputed for a grid.
+impl Neighborhood for Visibility {
+    fn neighbors(
+        &self,
+        position: (usize, usize),
+        _: (usize, usize),
+        out: &mut Vec<(usize, usize)>,
+    ) {
+        out.extend(self.visible(position));
+    }
+}
+
+impl<N: Neighborhood + ?Sized> Neighborhood for Box<N> {
+    fn neighbors(
+        &self,
+        position: (usize, usize),
+        dimensions: (usize, usize),
+        out: &mut Vec<(usize, usize)>,
+    ) {
+        (**self).neighbors(position, dimensions, out)
+    }
+}
+
+/// An iterator over the neighbors of a cell, given to a [`Rule`].
+pub struct Neighbors<'a, T> {
+    grid: &'a Grid<T>,
+    positions: slice::Iter<'a, (usize, usize)>,
+}
+
+impl<'a, T> Iterator for Neighbors<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let grid = self.grid;
+        self.positions.next().map(|p| &grid[*p])
+    }
+}
+
+/// Decides the next state of a cell from its current state and the
+/// states of its neighbors.
+pub trait Rule<T> {
+    fn next(&self, cell: &T, neighbors: Neighbors<'_, T>) -> T;
+}
+
+impl<T, F> Rule<T> for F
+where
+    F: Fn(&T, Neighbors<'_, T>) -> T,
+{
+    fn next(&self, cell: &T, neighbors: Neighbors<'_, T>) -> T {
+        self(cell, neighbors)
+    }
+}
+
+/// How a run of an automaton ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The grid stopped changing; `generation` is the first generation
+    /// that stays the same when stepped.
+    Stable { generation: usize },
+    /// The grid at generation `start` came back after `period`
+    /// generations, and will keep doing so forever.
+    Cycle { start: usize, period: usize },
+    /// The grid neither settled nor repeated itself before the limit.
+    LimitReached { generation: usize },
+}
+
+/// A cellular automaton over a grid, stepping every cell at once
+/// according to a rule.
+///
+/// The automaton keeps two grids and swaps them on every step, so
+/// stepping does not allocate.
+///
+/// # Examples
+///
+/// ```
+/// use utils::automaton::{Automaton, Neighbors, Offsets, Outcome};
+/// use utils::grid::Grid;
+///
+/// // Conway's Game of Life.
+/// let life = |alive: &bool, neighbors: Neighbors<bool>| {
+///     let n = neighbors.filter(|a| **a).count();
+///     n == 3 || (*alive && n == 2)
+/// };
+/// let parse = |s| Grid::parse(s, |c| Some(c == '#')).unwrap();
+///
+/// let mut block = Automaton::new(parse("....\n.##.\n.##.\n...."), Offsets::moore(), life);
+/// assert_eq!(block.run_until_stable(100), Outcome::Stable { generation: 0 });
+///
+/// let mut blinker = Automaton::new(parse(".....\n..#..\n..#..\n..#..\n....."), Offsets::moore(), life);
+/// assert_eq!(blinker.run_until_stable(100), Outcome::Cycle { start: 0, period: 2 });
+/// assert_eq!(blinker.generation(), 2);
+/// ```
+pub struct Automaton<T, N, R> {
+    current: Grid<T>,
+    next: Grid<T>,
+    neighborhood: N,
+    rule: R,
+    generation: usize,
+    neighbors: Vec<(usize, usize)>,
+}
+
+impl<T, N, R> Automaton<T, N, R>
+where
+    T: Clone + PartialEq,
+    N: Neighborhood,
+    R: Rule<T>,
+{
+    pub fn new(grid: Grid<T>, neighborhood: N, rule: R) -> Automaton<T, N, R> {
+        Automaton {
+            next: grid.clone(),
+            current: grid,
+            neighborhood,
+            rule,
+            generation: 0,
+            neighbors: vec![],
+        }
+    }
+
+    /// The grid of the current generation.
+    pub fn grid(&self) -> &Grid<T> {
+        &self.current
+    }
+
+    /// The number of steps taken so far.
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// Step every cell once, returning the number of cells that changed.
+    pub fn step(&mut self) -> usize {
+        let changes = advance(
+            &self.neighborhood,
+            &self.rule,
+            &mut self.neighbors,
+            &self.current,
+            &mut self.next,
+        );
+        std::mem::swap(&mut self.current, &mut self.next);
+        self.generation += 1;
+        changes
+    }
+
+    /// Check if stepping `steps` times from `snapshot` gives the current
+    /// grid, without touching the generation the automaton is at.
+    fn replays_to_current(&mut self, snapshot: &Grid<T>, steps: usize) -> bool {
+        let mut from = snapshot.clone();
+        let mut to = snapshot.clone();
+        for _ in 0..steps {
+            advance(
+                &self.neighborhood,
+                &self.rule,
+                &mut self.neighbors,
+                &from,
+                &mut to,
+            );
+            std::mem::swap(&mut from, &mut to);
+        }
+        from == self.current
+    }
+
+    /// Step until the grid stops changing, starts repeating itself, or
+    /// `max_generations` more generations have passed. Generations in the
+    /// outcome count from the creation of the automaton, not from the
+    /// start of this run.
+    ///
+    /// Repetitions are found by remembering the hash of every grid seen
+    /// during the run. When a hash comes back, the earlier grid is
+    /// replayed from the start of the run and compared in full, so hash
+    /// collisions are never reported as cycles.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::automaton::{Automaton, Neighbors, Offsets, Outcome};
+    /// use utils::grid::Grid;
+    ///
+    /// let blink = |alive: &bool, _: Neighbors<bool>| !*alive;
+    /// let grid = Grid::parse("#.", |c| Some(c == '#')).unwrap();
+    /// let mut a = Automaton::new(grid, Offsets::moore(), blink);
+    /// assert_eq!(a.run_until_stable(1), Outcome::LimitReached { generation: 1 });
+    /// assert_eq!(a.run_until_stable(5), Outcome::Cycle { start: 1, period: 2 });
+    /// ```
+    pub fn run_until_stable(&mut self, max_generations: usize) -> Outcome
+    where
+        T: Hash,
+    {
+        self.run_until_stable_with(max_generations, |_, _| ())
+    }
+
+    /// Like [`Automaton::run_until_stable`], but calling `observe` with
+    /// the current generation and its grid before the first step and
+    /// after every step.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::automaton::{Automaton, Neighbors, Offsets, Outcome};
+    /// use utils::grid::Grid;
+    ///
+    /// let spread = |alive: &bool, mut neighbors: Neighbors<bool>| *alive || neighbors.any(|a| *a);
+    /// let grid = Grid::parse("#...", |c| Some(c == '#')).unwrap();
+    /// let mut alive = vec![];
+    /// let mut a = Automaton::new(grid, Offsets::von_neumann(), spread);
+    /// let outcome = a.run_until_stable_with(10, |generation, grid| {
+    ///     alive.push((generation, grid.cells().filter(|a| **a).count()))
+    /// });
+    /// assert_eq!(outcome, Outcome::Stable { generation: 3 });
+    /// assert_eq!(alive, vec![(0, 1), (1, 2), (2, 3), (3, 4), (4, 4)]);
+    /// ```
+    pub fn run_until_stable_with<F>(&mut self, max_generations: usize, mut observe: F) -> Outcome
+    where
+        T: Hash,
+        F: FnMut(usize, &Grid<T>),
+    {
+        let first = self.generation;
+        let snapshot = self.current.clone();
+        let mut seen: HashMap<u64, usize> = HashMap::new();
+        seen.insert(hash(&self.current), first);
+        observe(self.generation, &self.current);
+        let end = first.saturating_add(max_generations);
+        while self.generation < end {
+            let changes = self.step();
+            observe(self.generation, &self.current);
+            if changes == 0 {
+                return Outcome::Stable {
+                    generation: self.generation - 1,
+                };
+            }
+            if let Some(start) = seen.insert(hash(&self.current), self.generation) {
+                if self.replays_to_current(&snapshot, start - first) {
+                    return Outcome::Cycle {
+                        start,
+                        period: self.generation - start,
+                    };
+                }
+            }
+        }
+        Outcome::LimitReached {
+            generation: self.generation,
+        }
+    }
+}
+
+/// Step every cell of `from` once into `to`, returning the number of
+/// cells that changed.
+fn advance<T, N, R>(
+    neighborhood: &N,
+    rule: &R,
+    scratch: &mut Vec<(usize, usize)>,
+    from: &Grid<T>,
+    to: &mut Grid<T>,
+) -> usize
+where
+    T: PartialEq,
+    N: Neighborhood,
+    R: Rule<T>,
+{
+    let dimensions = from.dimensions();
+    let (height, width) = dimensions;
+    let mut changes = 0;
+    for row in 0..height {
+        for column in 0..width {
+            let p = (row, column);
+            scratch.clear();
+            neighborhood.neighbors(p, dimensions, scratch);
+            let neighbors = Neighbors {
+                grid: from,
+                positions: scratch.iter(),
+            };
+            let next = rule.next(&from[p], neighbors);
+            if next != from[p] {
+                changes += 1;
+            }
+            to[p] = next;
+        }
+    }
+    changes
+}
+
+fn hash<T: Hash>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
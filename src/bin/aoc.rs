@@ -0,0 +1,74 @@
+use std::{
+    env, fs, io,
+    process::{self, Command},
+};
+
+use utils::parse::ParseError;
+use utils::vm::{debug::Debugger, dot, Program};
+
+fn main() -> Result<(), String> {
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(|s| s.as_str()) {
+        Some("validate") if args.len() == 4 => validate(&args[2], &args[3]),
+        Some("vm-debug") if args.len() == 3 => vm_debug(&args[2]),
+        Some("vm-cfg") if args.len() == 3 => vm_cfg(&args[2]),
+        _ => Err(format!(
+            "Usage: {0} validate <day> <input>\n       {0} vm-debug <program>\n       {0} vm-cfg <program>",
+            args[0]
+        )),
+    }
+}
+
+/// Check an input against the schema declared by the day, by running the
+/// day's binary, which is built next to this one, with `--validate`.
+fn validate(day: &str, input_file: &str) -> Result<(), String> {
+    let known = day
+        .strip_prefix("day")
+        .map(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+        .unwrap_or(false);
+    if !known {
+        return Err(format!("expected a day like day8, but found {}", day));
+    }
+    let binary = env::current_exe()
+        .map_err(|e| format!("failed to find the aoc binary: {}", e))?
+        .with_file_name(format!("{}{}", day, env::consts::EXE_SUFFIX));
+    if !binary.exists() {
+        return Err(format!(
+            "no binary for {} at {}, build it with cargo build --bin {}",
+            day,
+            binary.display(),
+            day
+        ));
+    }
+    let status = Command::new(&binary)
+        .arg("--validate")
+        .arg(input_file)
+        .status()
+        .map_err(|e| format!("failed to run {}: {}", binary.display(), e))?;
+    if !status.success() {
+        // The day has already reported what is wrong with the input.
+        process::exit(status.code().unwrap_or(1));
+    }
+    Ok(())
+}
+
+/// Parse a program for the handheld game console from a file.
+fn read_program(program_file: &str) -> Result<Program, String> {
+    let content = fs::read_to_string(program_file)
+        .map_err(|err| format!("failed to read program: {}", err))?;
+    content.parse().map_err(|e: ParseError| e.render(&content))
+}
+
+fn vm_debug(program_file: &str) -> Result<(), String> {
+    let program = read_program(program_file)?;
+    let stdin = io::stdin();
+    Debugger::new(&program)
+        .run(stdin.lock(), io::stdout())
+        .map_err(|e| format!("failed to run the debugger: {}", e))
+}
+
+fn vm_cfg(program_file: &str) -> Result<(), String> {
+    let program = read_program(program_file)?;
+    print!("{}", dot::cfg(&program));
+    Ok(())
+}
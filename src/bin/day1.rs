@@ -1,17 +1,25 @@
+use utils::parse::{self, Separator};
+use utils::schema::{self, Schema, Token};
+
 fn main() -> Result<(), String> {
+    if let Some(result) = schema::validate_args("day1", schema) {
+        return result;
+    }
     let (part, content) = utils::parse_args()?;
-    let input = content
-        .lines()
-        .map(|l| {
-            l.parse::<u32>()
-                .map_err(|e| format!("failed to parse number: {}", e))
-        })
-        .collect::<Result<Vec<u32>, String>>()?;
+    let input =
+        parse::numbers::<u32>(&content, Separator::Newline).map_err(|e| e.render(&content))?;
     utils::run(part1, part2, part, input);
 
     Ok(())
 }
 
+/// The expense report: at least two entries, each below 2020.
+fn schema() -> Schema {
+    use Token::*;
+
+    Schema::counted(2.., Schema::Lines(vec![Between(1, 2019)]))
+}
+
 fn part1(numbers: Vec<u32>) -> u32 {
     for i in numbers.iter() {
         for j in numbers.iter() {
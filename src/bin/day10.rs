@@ -1,19 +1,28 @@
 use std::str::FromStr;
 
+use utils::parse::{self, ParseError, Separator};
+use utils::schema::{self, Schema, Token};
+
 fn main() -> Result<(), String> {
+    if let Some(result) = schema::validate_args("day10", schema) {
+        return result;
+    }
     let (part, content) = utils::parse_args()?;
-    let input = parse_input(&content)?;
+    let input = parse_input(&content).map_err(|e| e.render(&content))?;
     utils::run(part1, part2, part, input);
     Ok(())
 }
 
-fn parse_input(s: &str) -> Result<Vec<u32>, String> {
-    s.lines()
-        .map(|l| {
-            l.parse()
-                .map_err(|e| format!("failed to parse {} as a number: {}", l, e))
-        })
-        .collect()
+/// The joltage ratings of the adapters: at most a couple hundred of
+/// them, each below 256 jolts.
+fn schema() -> Schema {
+    use Token::*;
+
+    Schema::counted(1.., Schema::Lines(vec![Between(1, 255)]))
+}
+
+fn parse_input(s: &str) -> Result<Vec<u32>, ParseError> {
+    parse::numbers(s, Separator::Newline)
 }
 
 fn part1(input: Vec<u32>) -> Result<u64, String> {
@@ -101,4 +110,19 @@ mod tests {
         let input = parse_input(TEST_STR_LONG).expect("should not fail");
         assert_eq!(find_differences(input), Ok((22, 0, 10)));
     }
+
+    #[test]
+    fn test_schema() {
+        assert_eq!(schema().validate(TEST_STR_SHORT), Ok(()));
+        let long: Vec<String> = (0..1000).map(|n| (n % 255 + 1).to_string()).collect();
+        assert_eq!(schema().validate(&long.join("\n")), Ok(()));
+        // Day 9 has much larger numbers, whatever the number of lines.
+        let day9: Vec<String> = (0..1000u64).map(|n| (n * 68_448_374).to_string()).collect();
+        let errors = schema().validate(&day9.join("\n")).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "expected a line like <number from 1 to 255>"
+        );
+        assert!(errors.len() > 1);
+    }
 }
@@ -1,42 +1,91 @@
 use core::fmt;
-use std::{collections::HashMap, str::FromStr};
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    rc::Rc,
+    str::FromStr,
+};
+
+use utils::animate::{Animation, Color, Sprite};
+use utils::automaton::{Automaton, Neighborhood, Neighbors, Offsets, Outcome, Rule};
+use utils::grid::{Grid, Visibility};
+use utils::image::{Encoder, Frames, Rgb};
+use utils::parse::ParseError;
+use utils::schema::{self, Schema};
+use utils::Options;
 
 fn main() -> Result<(), String> {
-    let (part, content) = utils::parse_args()?;
-    let input = content.parse()?;
-    utils::run(part1, part2, part, input);
+    if let Some(result) = schema::validate_args("day11", schema) {
+        return result;
+    }
+    let (part, content, options) = utils::parse_args_with_options()?;
+    let room = content
+        .parse()
+        .map_err(|e: ParseError| e.render(&content))?;
+    let recorder = Rc::new(RefCell::new(Recorder::from_options(&options)?));
+    utils::run(
+        part1,
+        part2,
+        part,
+        Simulation {
+            room,
+            options,
+            recorder,
+        },
+    );
     Ok(())
 }
 
-fn part1(mut input: Room) -> Result<i64, String> {
-    loop {
-        input.step(utils::Part::One);
-        if input.stable {
-            break;
-        }
-    }
-    Ok(input
-        .positions
-        .values()
-        .filter(|v| v.map_or_else(|| false, |s| s == Seat::Occupied))
-        .count() as i64)
+/// The seat layout.
+fn schema() -> Schema {
+    Schema::Grid("L.#")
 }
 
-fn part2(mut input: Room) -> Result<i64, String> {
-    loop {
-        input.step(utils::Part::Two);
-        if input.stable {
-            break;
-        }
+/// The number of generations to simulate before giving up on the seating
+/// ever settling.
+const MAX_GENERATIONS: usize = 10_000;
+
+fn part1(input: Simulation) -> Result<Report, String> {
+    input.simulate("Part 1", SeatRules::adjacent())
+}
+
+fn part2(input: Simulation) -> Result<Report, String> {
+    input.simulate("Part 2", SeatRules::line_of_sight())
+}
+
+/// A room together with the options adjusting the rules for it, and
+/// where to draw the generations.
+///
+/// The recorder is shared by both parts, so that running both draws
+/// part 2 after part 1 instead of over it.
+#[derive(Clone)]
+struct Simulation {
+    room: Room,
+    options: Options,
+    recorder: Rc<RefCell<Recorder<Box<dyn Write>>>>,
+}
+
+impl Simulation {
+    /// Simulate the room with the rules, drawing the generations under
+    /// `title`.
+    fn simulate(self, title: &str, rules: SeatRules) -> Result<Report, String> {
+        let rules = rules.with_options(&self.options)?;
+        let mut recorder = self.recorder.borrow_mut();
+        recorder
+            .section(title)
+            .map_err(|e| format!("failed to record the seating: {}", e))?;
+        rules.simulate(self.room, &mut recorder)
     }
-    Ok(input
-        .positions
-        .values()
-        .filter(|v| v.map_or_else(|| false, |s| s == Seat::Occupied))
-        .count() as i64)
 }
 
+/// How the seating ended up once it settled.
 #[derive(Clone, Copy, Debug, PartialEq)]
+struct Report {
+    occupied: usize,
+    generations: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 enum Seat {
     Empty,
     Occupied,
@@ -44,202 +93,296 @@ enum Seat {
 
 #[derive(Clone, Debug, PartialEq)]
 struct Room {
-    positions: HashMap<(i32, i32), Option<Seat>>,
-    max_row: usize,
-    max_column: usize,
-    stable: bool,
+    positions: Grid<Option<Seat>>,
 }
 
-impl Room {
-    fn step(&mut self, part: utils::Part) {
-        let mut changes = 0;
-        let seats_to_check: Vec<(i32, i32)> = self
-            .positions
-            .iter()
-            .filter(|(_, v)| v.is_some())
-            .map(|(k, _)| *k)
-            .collect();
-        let mut new_positions = self.positions.clone();
-        for seat in seats_to_check {
-            let new_value = match part {
-                utils::Part::One => self.check_seat(seat),
-                utils::Part::Two => self.check_seat_part2(seat),
-                _ => unimplemented!(),
-            };
-            if self.positions[&seat] != new_value {
-                new_positions.insert(seat, new_value);
-                changes += 1;
-            }
+/// Which seats people look at when deciding whether to sit down.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Sight {
+    /// Every seat within the radius.
+    Adjacent,
+    /// The first seat within the radius in each of the eight directions.
+    LineOfSight,
+}
+
+impl FromStr for Sight {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "adjacent" => Ok(Sight::Adjacent),
+            "line-of-sight" => Ok(Sight::LineOfSight),
+            _ => Err(format!(
+                "expected either 'adjacent' or 'line-of-sight', found {}",
+                s
+            )),
         }
-        self.positions = new_positions;
-        self.stable = changes == 0;
     }
+}
 
-    fn check_seat(&self, s: (i32, i32)) -> Option<Seat> {
-        let neighbors = self.get_adjacent_cells(s);
-        match self.positions[&s] {
-            None => None,
-            Some(Seat::Occupied) => {
-                if neighbors
-                    .iter()
-                    .filter(|n| **n == Some(Seat::Occupied))
-                    .count()
-                    >= 4
-                {
-                    Some(Seat::Empty)
-                } else {
-                    Some(Seat::Occupied)
-                }
-            }
-            Some(Seat::Empty) => {
-                if neighbors.iter().all(|n| *n != Some(Seat::Occupied)) {
-                    Some(Seat::Occupied)
-                } else {
-                    Some(Seat::Empty)
-                }
-            }
+/// Whether people looking along a line of sight can see across the floor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Floor {
+    Transparent,
+    Opaque,
+}
+
+impl FromStr for Floor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "transparent" => Ok(Floor::Transparent),
+            "opaque" => Ok(Floor::Opaque),
+            _ => Err(format!(
+                "expected either 'transparent' or 'opaque', found {}",
+                s
+            )),
         }
     }
+}
 
-    fn check_seat_part2(&self, s: (i32, i32)) -> Option<Seat> {
-        let neighbors = self.get_neighbors(s);
-        match self.positions[&s] {
-            None => None,
-            Some(Seat::Occupied) => {
-                if neighbors
-                    .iter()
-                    .cloned()
-                    .filter(|n| *n == Seat::Occupied)
-                    .count()
-                    >= 5
-                {
-                    Some(Seat::Empty)
-                } else {
-                    Some(Seat::Occupied)
-                }
+/// The rules people follow when choosing where to sit.
+///
+/// Every rule can be overridden on the command line, e.g. with
+/// `--tolerance 3 --sight line-of-sight --radius 2 --floor opaque`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct SeatRules {
+    tolerance: usize,
+    sight: Sight,
+    /// How far people look, or `None` for a single seat away when looking
+    /// at adjacent seats and as far as possible along lines of sight.
+    radius: Option<usize>,
+    floor: Floor,
+}
+
+impl SeatRules {
+    /// The rules of part 1.
+    fn adjacent() -> SeatRules {
+        SeatRules {
+            tolerance: 4,
+            sight: Sight::Adjacent,
+            radius: None,
+            floor: Floor::Transparent,
+        }
+    }
+
+    /// The rules of part 2.
+    fn line_of_sight() -> SeatRules {
+        SeatRules {
+            tolerance: 5,
+            sight: Sight::LineOfSight,
+            radius: None,
+            floor: Floor::Transparent,
+        }
+    }
+
+    fn with_options(self, options: &Options) -> Result<SeatRules, String> {
+        Ok(SeatRules {
+            tolerance: options.get_or("tolerance", self.tolerance)?,
+            sight: options.get_or("sight", self.sight)?,
+            radius: options.get("radius")?.or(self.radius),
+            floor: options.get_or("floor", self.floor)?,
+        })
+    }
+
+    /// Set up a simulation of people moving between the seats of a room.
+    fn automaton(&self, room: Room) -> Automaton<Option<Seat>, Box<dyn Neighborhood>, SeatRule> {
+        let neighborhood: Box<dyn Neighborhood> = match self.sight {
+            Sight::Adjacent => Box::new(Offsets::moore_radius(self.radius.unwrap_or(1))),
+            Sight::LineOfSight => {
+                let transparent = self.floor == Floor::Transparent;
+                Box::new(Visibility::within(
+                    &room.positions,
+                    |p| transparent && p.is_none(),
+                    self.radius.unwrap_or(usize::MAX),
+                ))
             }
-            Some(Seat::Empty) => {
-                if neighbors.iter().all(|n| *n != Seat::Occupied) {
-                    Some(Seat::Occupied)
-                } else {
-                    Some(Seat::Empty)
-                }
+        };
+        let rule = SeatRule {
+            tolerance: self.tolerance,
+        };
+        Automaton::new(room.positions, neighborhood, rule)
+    }
+
+    /// Run the simulation until the seating settles, recording every
+    /// generation with `recorder`.
+    fn simulate<W: Write>(&self, room: Room, recorder: &mut Recorder<W>) -> Result<Report, String> {
+        let mut seats = self.automaton(room);
+        let mut result = Ok(());
+        let outcome = seats.run_until_stable_with(MAX_GENERATIONS, |generation, grid| {
+            if result.is_ok() {
+                result = recorder.record(generation, grid);
             }
+        });
+        result
+            .and_then(|_| recorder.finish())
+            .map_err(|e| format!("failed to record the seating: {}", e))?;
+        match outcome {
+            Outcome::Stable { generation } => Ok(Report {
+                occupied: seats
+                    .grid()
+                    .cells()
+                    .filter(|v| **v == Some(Seat::Occupied))
+                    .count(),
+                generations: generation,
+            }),
+            Outcome::Cycle { start, period } => Err(format!(
+                "the seating never settles, it repeats every {} generations from generation {}",
+                period, start
+            )),
+            Outcome::LimitReached { generation } => Err(format!(
+                "the seating did not settle within {} generations",
+                generation
+            )),
         }
     }
+}
 
-    fn get_adjacent_cells(&self, (row, col): (i32, i32)) -> Vec<Option<Seat>> {
-        let mut neighbors = vec![];
-        for r in (-1..2).map(|i| row + i) {
-            for c in (-1..2).map(|i| col + i) {
-                if (r, c) == (row, col) {
-                    continue;
-                }
-                if let Some(p) = self.positions.get(&(r, c)) {
-                    neighbors.push(*p);
-                }
-            }
+/// Where the generations of a simulation are drawn, if anywhere.
+struct Recorder<W: Write> {
+    animation: Option<Animation<W>>,
+    images: Option<Frames>,
+}
+
+impl<W: Write> Recorder<W> {
+    /// A recorder that draws nothing.
+    #[cfg(test)]
+    fn none() -> Recorder<W> {
+        Recorder {
+            animation: None,
+            images: None,
         }
-        neighbors
     }
 
-    fn get_neighbors(&self, (row, col): (i32, i32)) -> Vec<Seat> {
-        enum Direction {
-            N,
-            NE,
-            E,
-            SE,
-            S,
-            SW,
-            W,
-            NW,
+    fn record(&mut self, generation: usize, seats: &Grid<Option<Seat>>) -> io::Result<()> {
+        if let Some(animation) = &mut self.animation {
+            animation.frame(generation, seats)?;
+        }
+        if let Some(images) = &mut self.images {
+            images.frame(seats, &seat_color)?;
         }
+        Ok(())
+    }
 
-        fn get_neighbor_in_direction(
-            (row, col): (i32, i32),
-            direction: Direction,
-            room: &Room,
-        ) -> Option<Seat> {
-            let pos = match direction {
-                Direction::N => (row - 1, col),
-                Direction::NE => (row - 1, col + 1),
-                Direction::E => (row, col + 1),
-                Direction::SE => (row + 1, col + 1),
-                Direction::S => (row + 1, col),
-                Direction::SW => (row + 1, col - 1),
-                Direction::W => (row, col - 1),
-                Direction::NW => (row - 1, col - 1),
-            };
-            match room.positions.get(&pos) {
-                None => None,
-                Some(None) => get_neighbor_in_direction(pos, direction, room),
-                Some(Some(s)) => Some(*s),
-            }
+    fn section(&mut self, title: &str) -> io::Result<()> {
+        match &mut self.animation {
+            Some(animation) => animation.section(title),
+            None => Ok(()),
         }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        match &mut self.animation {
+            Some(animation) => animation.finish(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Recorder<Box<dyn Write>> {
+    /// Animate the seating as asked for by `--visualize` or `--frames`,
+    /// and write images of every generation to files starting with the
+    /// prefix given by `--images`.
+    fn from_options(options: &Options) -> Result<Recorder<Box<dyn Write>>, String> {
+        let images = match options.get::<String>("images")? {
+            Some(prefix) => Some(Frames::new(&prefix, Encoder::from_options(options)?)),
+            None => None,
+        };
+        Ok(Recorder {
+            animation: Animation::from_options(options)?,
+            images,
+        })
+    }
+}
 
-        vec![
-            Direction::N,
-            Direction::NE,
-            Direction::E,
-            Direction::SE,
-            Direction::S,
-            Direction::SW,
-            Direction::W,
-            Direction::NW,
-        ]
-        .into_iter()
-        .filter_map(|d| get_neighbor_in_direction((row, col), d, self))
-        .collect::<Vec<Seat>>()
+fn seat_color(seat: &Option<Seat>) -> Rgb {
+    match seat {
+        Some(Seat::Empty) => Rgb(46, 160, 67),
+        Some(Seat::Occupied) => Rgb(207, 34, 46),
+        None => Rgb::gray(230),
+    }
+}
+
+/// How many occupied seats around them people tolerate before leaving.
+#[derive(Clone, Copy, Debug)]
+struct SeatRule {
+    tolerance: usize,
+}
+
+impl Rule<Option<Seat>> for SeatRule {
+    fn next(&self, seat: &Option<Seat>, neighbors: Neighbors<'_, Option<Seat>>) -> Option<Seat> {
+        if seat.is_none() {
+            return None;
+        }
+        let occupied = neighbors.filter(|n| **n == Some(Seat::Occupied)).count();
+        next_seat(*seat, occupied, self.tolerance)
+    }
+}
+
+/// Decide what happens to a seat given the number of occupied seats
+/// around it and how many of them people tolerate.
+fn next_seat(seat: Option<Seat>, occupied_neighbors: usize, tolerance: usize) -> Option<Seat> {
+    match seat {
+        None => None,
+        Some(Seat::Occupied) if occupied_neighbors >= tolerance => Some(Seat::Empty),
+        Some(Seat::Empty) if occupied_neighbors == 0 => Some(Seat::Occupied),
+        s => s,
+    }
+}
+
+impl Sprite for Seat {
+    fn symbol(&self) -> char {
+        match self {
+            Seat::Empty => 'L',
+            Seat::Occupied => '#',
+        }
+    }
+
+    fn color(&self) -> Option<Color> {
+        match self {
+            Seat::Empty => Some(Color::Green),
+            Seat::Occupied => Some(Color::Red),
+        }
     }
 }
 
 impl FromStr for Room {
-    type Err = String;
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut room = HashMap::new();
-        let mut max_row = 0;
-        let mut max_column = 0;
-        for (row, l) in s.lines().enumerate() {
-            max_row = row;
-            l.chars().enumerate().for_each(|(col, c)| {
-                match c {
-                    '#' => room.insert((row as i32, col as i32), Some(Seat::Occupied)),
-                    'L' => room.insert((row as i32, col as i32), Some(Seat::Empty)),
-                    '.' => room.insert((row as i32, col as i32), None),
-                    _ => room.insert((row as i32, col as i32), None),
-                };
-                max_column = col;
-            })
-        }
-        Ok(Room {
-            positions: room,
-            max_row,
-            max_column,
-            stable: false,
-        })
+        let positions = Grid::parse(s, |c| match c {
+            '#' => Some(Some(Seat::Occupied)),
+            'L' => Some(Some(Seat::Empty)),
+            _ => Some(None),
+        })?;
+        Ok(Room { positions })
     }
 }
 
 impl fmt::Display for Room {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), std::fmt::Error> {
-        for row in 0..self.max_row + 1 {
-            for column in 0..self.max_column + 1 {
-                match self.positions.get(&(row as i32, column as i32)).unwrap() {
-                    Some(Seat::Empty) => write!(f, "{}", 'L')?,
-                    Some(Seat::Occupied) => write!(f, "{}", '#')?,
-                    None => write!(f, "{}", '.')?,
-                };
-            }
-            write!(f, "\n")?;
-        }
-        Ok(())
+        let chars = self.positions.map(|p| match p {
+            Some(Seat::Empty) => 'L',
+            Some(Seat::Occupied) => '#',
+            None => '.',
+        });
+        write!(f, "{}", chars)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use utils::animate::Mode;
+
+    fn simulation(room: Room, args: &[&str]) -> Simulation {
+        Simulation {
+            room,
+            options: Options::parse(args).unwrap(),
+            recorder: Rc::new(RefCell::new(Recorder::none())),
+        }
+    }
 
     const TEST_STR: &str = "L.LL.LL.LL
 LLLLLLL.LL
@@ -254,7 +397,7 @@ L.LLLLL.LL";
 
     #[test]
     fn test_step() {
-        let mut r: Room = "#.##.##.##
+        let r: Room = "#.##.##.##
 #######.##
 #.#.#..#..
 ####.##.##
@@ -278,19 +421,26 @@ L.L.L..L..
 #.#LLLL.##"
             .parse()
             .unwrap();
-        r.step(utils::Part::One);
-        assert_eq!(r, expected);
+        let mut seats = SeatRules::adjacent().automaton(r);
+        seats.step();
+        assert_eq!(seats.grid(), &expected.positions);
     }
 
     #[test]
     fn test_part_one() {
         let r = TEST_STR.parse().unwrap();
-        assert_eq!(part1(r), Ok(37))
+        assert_eq!(
+            part1(simulation(r, &[])),
+            Ok(Report {
+                occupied: 37,
+                generations: 5
+            })
+        )
     }
 
     #[test]
     fn test_step_part_two() {
-        let mut r: Room = "#.##.##.##
+        let r: Room = "#.##.##.##
 #######.##
 #.#.#..#..
 ####.##.##
@@ -314,13 +464,96 @@ LLLLLLLLL#
 #.LLLLL.L#"
             .parse()
             .unwrap();
-        r.step(utils::Part::Two);
-        assert_eq!(r, expected);
+        let mut seats = SeatRules::line_of_sight().automaton(r);
+        seats.step();
+        assert_eq!(seats.grid(), &expected.positions);
     }
 
     #[test]
     fn test_part_two() {
         let r = TEST_STR.parse().unwrap();
-        assert_eq!(part2(r), Ok(26))
+        assert_eq!(
+            part2(simulation(r, &[])),
+            Ok(Report {
+                occupied: 26,
+                generations: 6
+            })
+        )
+    }
+
+    #[test]
+    fn test_seat_rules_options() {
+        let options = Options::parse(&["--tolerance", "5", "--sight", "line-of-sight"]).unwrap();
+        assert_eq!(
+            SeatRules::adjacent().with_options(&options),
+            Ok(SeatRules::line_of_sight())
+        );
+        let options = Options::parse(&["--sight", "sideways"]).unwrap();
+        assert!(SeatRules::adjacent().with_options(&options).is_err());
+    }
+
+    #[test]
+    fn test_opaque_floor_is_adjacent() {
+        let r: Room = TEST_STR.parse().unwrap();
+        let opaque = simulation(r.clone(), &["--tolerance", "4", "--floor", "opaque"]);
+        assert_eq!(
+            opaque.options.get::<Floor>("floor"),
+            Ok(Some(Floor::Opaque))
+        );
+        assert_eq!(part2(opaque), part1(simulation(r, &[])));
+    }
+
+    #[test]
+    fn test_larger_radius() {
+        // With a radius of 2 the three middle seats see at least three
+        // neighbors and leave, while the outer seats see two and stay.
+        let r: Room = "LLLLL".parse().unwrap();
+        let report = part1(simulation(
+            r.clone(),
+            &["--radius", "2", "--tolerance", "3"],
+        ));
+        assert_eq!(report.map(|r| r.occupied), Ok(2));
+        let report = part1(simulation(r, &["--radius", "1", "--tolerance", "3"]));
+        assert_eq!(report.map(|r| r.occupied), Ok(5));
+    }
+
+    #[test]
+    fn test_oscillating_seats() {
+        // Nobody tolerates a neighbor, so the two seats take turns.
+        let r: Room = "LL".parse().unwrap();
+        let rules = SeatRules {
+            tolerance: 1,
+            ..SeatRules::adjacent()
+        };
+        assert_eq!(
+            rules.simulate(r, &mut Recorder::<Vec<u8>>::none()),
+            Err(
+                "the seating never settles, it repeats every 2 generations from generation 0"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_frames() {
+        let r: Room = "L.\nLL".parse().unwrap();
+        let mut frames = vec![];
+        let mut recorder = Recorder {
+            animation: Some(Animation::new(&mut frames, Mode::Dump)),
+            images: None,
+        };
+        let report = SeatRules::adjacent().simulate(r, &mut recorder);
+        drop(recorder);
+        assert_eq!(
+            report,
+            Ok(Report {
+                occupied: 3,
+                generations: 1
+            })
+        );
+        assert_eq!(
+            String::from_utf8(frames).unwrap(),
+            "Generation 0\nL.\nLL\n\nGeneration 1\n#.\n##\n\nGeneration 2\n#.\n##\n\n"
+        );
     }
 }
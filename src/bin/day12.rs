@@ -1,14 +1,28 @@
+use std::fmt;
 use std::str::FromStr;
 
+use utils::parse::{self, ParseError, Span};
+use utils::schema::{self, Schema, Token};
+
 fn main() -> Result<(), String> {
+    if let Some(result) = schema::validate_args("day12", schema) {
+        return result;
+    }
     let (part, content) = utils::parse_args()?;
-    let input = parse_input(&content)?;
+    let input = parse_input(&content).map_err(|e| e.render(&content))?;
     utils::run(part1, part2, part, input);
     Ok(())
 }
 
-fn parse_input(s: &str) -> Result<Vec<Direction>, String> {
-    s.lines().map(|l| l.parse()).collect()
+/// A navigation instruction on every line.
+fn schema() -> Schema {
+    use Token::*;
+
+    Schema::Lines(vec![OneOf("NESWLRF"), Digits])
+}
+
+fn parse_input(s: &str) -> Result<Vec<Direction>, ParseError> {
+    parse::lines(s, str::parse)
 }
 
 fn part1(input: Vec<Direction>) -> Result<i32, String> {
@@ -21,7 +35,7 @@ fn part2(_input: Vec<Direction>) -> Result<i32, String> {
     unimplemented!()
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 enum Direction {
     Forward(usize),
     North(usize),
@@ -33,18 +47,22 @@ enum Direction {
 }
 
 impl FromStr for Direction {
-    type Err = String;
+    type Err = ParseError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.len() < 2 {
-            return Err(format!(
-                "invalid length of line, expected at least 2 characters but got: {}",
-                s
+        let split = s.chars().next().map_or(0, char::len_utf8);
+        if split == 0 || split == s.len() {
+            return Err(ParseError::new(
+                Span::end_of(s),
+                "expected a direction followed by a number",
             ));
         }
-        let (d, num) = s.split_at(1);
-        let parsed_num = num
-            .parse()
-            .map_err(|e| format!("failed to parse {} as a number: {}", num, e))?;
+        let (d, num) = s.split_at(split);
+        let parsed_num = num.parse().map_err(|e| {
+            ParseError::new(
+                Span::within(s, num),
+                format!("failed to parse {} as a number: {}", num, e),
+            )
+        })?;
         let dir = match d {
             "F" => Direction::Forward(parsed_num as usize),
             "N" => Direction::North(parsed_num as usize),
@@ -53,15 +71,29 @@ impl FromStr for Direction {
             "W" => Direction::West(parsed_num as usize),
             "R" => Direction::Right(parsed_num),
             "L" => Direction::Left(parsed_num),
-            _ => Err(format!(
-                "invalid direction, expected one of F, N, E, S, W, R or L but found: {}",
-                d
+            _ => Err(ParseError::new(
+                Span::within(s, d),
+                "invalid direction, expected one of F, N, E, S, W, R or L",
             ))?,
         };
         Ok(dir)
     }
 }
 
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Direction::Forward(n) => write!(f, "F{}", n),
+            Direction::North(n) => write!(f, "N{}", n),
+            Direction::East(n) => write!(f, "E{}", n),
+            Direction::South(n) => write!(f, "S{}", n),
+            Direction::West(n) => write!(f, "W{}", n),
+            Direction::Right(deg) => write!(f, "R{}", deg),
+            Direction::Left(deg) => write!(f, "L{}", deg),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 struct Ship {
     north: i32,
@@ -116,4 +148,29 @@ F11";
         let directions = parse_input(TEST_STR).expect("no fail");
         assert_eq!(part1(directions), Ok(25));
     }
+
+    #[test]
+    fn test_display_roundtrip() {
+        for (d, l) in parse_input(TEST_STR).unwrap().iter().zip(TEST_STR.lines()) {
+            assert_eq!(d.to_string(), l);
+            assert_eq!(parse::roundtrip(d), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_parse_error_span() {
+        let err = parse_input("F10\nN3\nX7").unwrap_err();
+        assert_eq!(err.span, Span::new(3, 1, 1));
+
+        let err = parse_input("F10\nNx3").unwrap_err();
+        assert_eq!(err.span, Span::new(2, 2, 2));
+        assert_eq!(
+            err.render("F10\nNx3"),
+            "error: failed to parse x3 as a number: invalid digit found in string
+ --> 2:2
+  |
+2 | Nx3
+  |  ^^"
+        );
+    }
 }
@@ -1,12 +1,25 @@
 use std::str::FromStr;
 
+use utils::schema::{self, Schema, Token};
+
 fn main() -> Result<(), String> {
+    if let Some(result) = schema::validate_args("day14", schema) {
+        return result;
+    }
     let (part, content) = utils::parse_args()?;
     let input = parse_input(&content)?;
     utils::run(part1, part2, part, input);
     Ok(())
 }
 
+/// A navigation instruction on every line, as this day still reads the
+/// input of day 12.
+fn schema() -> Schema {
+    use Token::*;
+
+    Schema::Lines(vec![OneOf("NESWLRF"), Digits])
+}
+
 fn parse_input(s: &str) -> Result<Vec<Direction>, String> {
     s.lines().map(|l| l.parse()).collect()
 }
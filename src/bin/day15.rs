@@ -1,19 +1,27 @@
 use std::collections::HashMap;
 
+use utils::parse::{self, Separator};
+use utils::schema::{self, Schema, Token};
+
 fn main() -> Result<(), String> {
+    if let Some(result) = schema::validate_args("day15", schema) {
+        return result;
+    }
     let (part, content) = utils::parse_args()?;
     utils::run(part1, part2, part, &content);
     Ok(())
 }
 
+/// The starting numbers, on a single line.
+fn schema() -> Schema {
+    use Token::*;
+
+    Schema::counted(1..=1, Schema::Lines(vec![ManyOf("0123456789,")]))
+}
+
 fn part1(input: &str) -> Result<u32, String> {
-    let start_numbers: Vec<u32> = input
-        .split(',')
-        .map(|n| {
-            n.parse()
-                .map_err(|e| format!("failed to parse {} as a number: {}", n, e))
-        })
-        .collect::<Result<Vec<u32>, String>>()?;
+    let start_numbers: Vec<u32> =
+        parse::numbers(input, Separator::Comma).map_err(|e| e.render(input))?;
     Ok(play(start_numbers, 2020))
 }
 
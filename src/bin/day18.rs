@@ -1,12 +1,24 @@
 use std::str::FromStr;
 
+use utils::schema::{self, Schema, Token};
+
 fn main() -> Result<(), String> {
+    if let Some(result) = schema::validate_args("day18", schema) {
+        return result;
+    }
     let (part, content) = utils::parse_args()?;
     let input = content.lines().map(|s| s.to_string()).collect();
     utils::run(part1, part2, part, input);
     Ok(())
 }
 
+/// An expression on every line.
+fn schema() -> Schema {
+    use Token::*;
+
+    Schema::Lines(vec![ManyOf("0123456789+*() ")])
+}
+
 fn eval(line: &str) -> u32 {
     let mut val = 0;
     let mut words = line.split(' ');
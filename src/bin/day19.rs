@@ -1,56 +1,93 @@
-use std::{collections::HashMap, str::FromStr};
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use utils::schema::{self, Schema, Token};
 
 fn main() -> Result<(), String> {
+    if let Some(result) = schema::validate_args("day19", schema) {
+        return result;
+    }
     let (part, content) = utils::parse_args()?;
     utils::run(part1, part2, part, content);
     Ok(())
 }
 
-fn part1(input: String) -> u32 {
+/// The rules, then the messages after a blank line.
+fn schema() -> Schema {
+    use Token::*;
+
+    Schema::Sections(vec![
+        Schema::Lines(vec![Digits, Literal(": "), Rest]),
+        Schema::Lines(vec![ManyOf("ab")]),
+    ])
+}
+
+fn part1(input: String) -> Result<u32, String> {
     let rules: String = input
         .lines()
         .take_while(|l| *l != "")
         .map(|l| l.to_string())
         .collect::<Vec<String>>()
         .join("\n");
-    let m: Matcher = rules.parse().unwrap();
-    input
-        .lines()
-        .skip_while(|l| *l != "")
-        .skip(1)
-        .filter(|l| {
-            let (s, b) = m.check(*l);
-            s == "" && b
-        })
-        .count() as u32
+    let m: Matcher = rules.parse()?;
+    let mut count = 0;
+    for l in input.lines().skip_while(|l| *l != "").skip(1) {
+        if m.matches(l)? {
+            count += 1;
+        }
+    }
+    Ok(count)
 }
 
-fn part2(_input: String) -> u32 {
+fn part2(_input: String) -> Result<u32, String> {
     unimplemented!()
 }
 
+/// Matchers are read from rules, or built by hand from literals with
+/// [`Matcher::then`] and [`Matcher::or`], which only the tests do.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(not(test), allow(dead_code))]
 enum Matcher {
-    Empty,
-    Literal(char),
+    Literal(String),
     Sequence(Box<Matcher>, Box<Matcher>),
     Alternative(Box<Matcher>, Box<Matcher>),
+    /// A rule as read, with the parts of each of its alternatives. An
+    /// alternative without parts never matches.
+    Rule(Vec<Vec<Part>>),
 }
 
-fn check_for(c: char) -> Matcher {
-    Matcher::Literal(c)
+/// A part of an alternative of a rule.
+#[derive(Clone, Debug, PartialEq)]
+enum Part {
+    /// Characters in quotes.
+    Literal(String),
+    /// Another rule, referred to by its id.
+    Rule(Matcher),
+}
+
+impl Part {
+    fn check<'a>(&self, s: &'a str) -> (&'a str, bool) {
+        match self {
+            Part::Literal(l) => match s.strip_prefix(l.as_str()) {
+                Some(rest) => (rest, true),
+                None => (s, false),
+            },
+            Part::Rule(m) => m.check(s),
+        }
+    }
+}
+
+#[cfg(test)]
+fn check_for<S: ToString>(s: S) -> Matcher {
+    Matcher::Literal(s.to_string())
 }
 
 impl Matcher {
     fn check<'a>(&self, s: &'a str) -> (&'a str, bool) {
         match self {
-            Matcher::Empty => (s, false),
-            Matcher::Literal(c) => {
-                if s.chars().next() == Some(*c) {
-                    return (&s[1..], true);
-                }
-                (s, false)
-            }
+            Matcher::Literal(l) => match s.strip_prefix(l.as_str()) {
+                Some(rest) => (rest, true),
+                None => (s, false),
+            },
             Matcher::Sequence(first, then) => {
                 if let (s1, true) = first.check(s) {
                     if let (s2, true) = then.check(s1) {
@@ -67,69 +104,152 @@ impl Matcher {
                 }
                 (s, false)
             }
+            Matcher::Rule(alternatives) => {
+                for parts in alternatives.iter().filter(|p| !p.is_empty()) {
+                    let mut rest = s;
+                    let matched = parts.iter().all(|part| match part.check(rest) {
+                        (r, true) => {
+                            rest = r;
+                            true
+                        }
+                        _ => false,
+                    });
+                    if matched {
+                        return (rest, true);
+                    }
+                }
+                (s, false)
+            }
         }
     }
 
-    fn then(self, matcher: Matcher) -> Matcher {
-        Matcher::Sequence(Box::new(self), Box::new(matcher))
+    /// Check if the whole of `s` matches, rejecting messages that are
+    /// not pure ASCII.
+    fn matches(&self, s: &str) -> Result<bool, String> {
+        if let Some((i, c)) = s.char_indices().find(|(_, c)| !c.is_ascii()) {
+            return Err(format!(
+                "expected an ASCII message, but found {:?} at byte {} in: {}",
+                c, i, s
+            ));
+        }
+        let (rest, matched) = self.check(s);
+        Ok(matched && rest.is_empty())
     }
 
-    fn or(self, matcher: Matcher) -> Matcher {
-        Matcher::Alternative(Box::new(self), Box::new(matcher))
+    /// Add a rule for this matcher and each of its parts to `rules`,
+    /// returning the id of the rule for this matcher.
+    ///
+    /// Matchers read from a rule set are written the way they were read,
+    /// so that reading them again gives the same matcher. Others are
+    /// written as a rule set matching the same messages.
+    fn collect_rules(&self, rules: &mut Vec<String>) -> usize {
+        let id = rules.len();
+        rules.push(String::new());
+        rules[id] = match self {
+            Matcher::Literal(l) => format!("\"{}\"", l),
+            Matcher::Sequence(first, then) => {
+                format!(
+                    "{} {}",
+                    first.collect_rules(rules),
+                    then.collect_rules(rules)
+                )
+            }
+            Matcher::Alternative(this, that) => {
+                format!(
+                    "{} | {}",
+                    this.collect_rules(rules),
+                    that.collect_rules(rules)
+                )
+            }
+            Matcher::Rule(alternatives) => {
+                let mut bodies = vec![];
+                for parts in alternatives {
+                    let mut words = vec![];
+                    for part in parts {
+                        words.push(match part {
+                            Part::Literal(l) => format!("\"{}\"", l),
+                            Part::Rule(m) => m.collect_rules(rules).to_string(),
+                        });
+                    }
+                    bodies.push(words.join(" "));
+                }
+                bodies.join(" | ")
+            }
+        };
+        id
     }
 
     fn parse_body(bodies: &HashMap<u32, String>, id: u32) -> Result<Self, String> {
         let body = bodies
             .get(&id)
             .ok_or_else(|| format!("could not find body for rule {}", id))?;
-        let mut matchers = vec![Matcher::Empty];
+        let mut alternatives = vec![vec![]];
         let mut memoized_matchers: HashMap<u32, Matcher> = HashMap::new();
 
-        fn combine_matcher(m1: Matcher, m2: Matcher) -> Matcher {
-            if m1 == Matcher::Empty {
-                m1.or(m2)
-            } else {
-                m1.then(m2)
-            }
-        }
-
         for w in body.trim().split(' ') {
             if w == "|" {
-                matchers.push(Matcher::Empty);
-            } else if w.len() > 2 && &w[0..1] == "\"" {
-                let c = w.bytes().skip(1).next().ok_or_else(|| {
-                    format!(
-                        "expected a single character inside quotes, but found: {}",
+                alternatives.push(vec![]);
+            } else if w.starts_with('"') {
+                let literal = w
+                    .strip_prefix('"')
+                    .and_then(|l| l.strip_suffix('"'))
+                    .filter(|l| !l.is_empty())
+                    .ok_or_else(|| {
+                        format!("expected characters inside quotes, but found: {}", w)
+                    })?;
+                if !literal.is_ascii() {
+                    return Err(format!(
+                        "expected only ASCII characters inside quotes, but found: {}",
                         w
-                    )
-                })?;
-                matchers
+                    ));
+                }
+                alternatives
                     .last_mut()
-                    .map(|curr| *curr = combine_matcher(curr.clone(), check_for(c as char)));
+                    .map(|curr| curr.push(Part::Literal(literal.to_string())));
             } else {
                 let id = w
                     .parse::<u32>()
                     .map_err(|e| format!("could not parse {} as a matcher id: {}", w, e))?;
                 match memoized_matchers.get(&id) {
                     Some(m) => {
-                        matchers
+                        alternatives
                             .last_mut()
-                            .map(|curr| *curr = combine_matcher(curr.clone(), m.clone()));
+                            .map(|curr| curr.push(Part::Rule(m.clone())));
                     }
                     None => {
                         let m = Matcher::parse_body(bodies, id)?;
                         memoized_matchers.insert(id, m.clone());
-                        matchers
-                            .last_mut()
-                            .map(|curr| *curr = combine_matcher(curr.clone(), m));
+                        alternatives.last_mut().map(|curr| curr.push(Part::Rule(m)));
                     }
                 }
             }
         }
 
-        Ok(matchers
-            .iter()
-            .fold(Matcher::Empty, |acc, m| acc.or(m.clone())))
+        Ok(Matcher::Rule(alternatives))
+    }
+}
+
+#[cfg(test)]
+impl Matcher {
+    fn then(self, matcher: Matcher) -> Matcher {
+        Matcher::Sequence(Box::new(self), Box::new(matcher))
+    }
+
+    fn or(self, matcher: Matcher) -> Matcher {
+        Matcher::Alternative(Box::new(self), Box::new(matcher))
+    }
+}
+
+impl fmt::Display for Matcher {
+    /// Write the matcher as a rule set with one rule per part, where rule 0
+    /// is the matcher itself.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut rules = vec![];
+        self.collect_rules(&mut rules);
+        for (id, body) in rules.iter().enumerate() {
+            writeln!(f, "{}: {}", id, body)?;
+        }
+        Ok(())
     }
 }
 
@@ -162,6 +282,7 @@ impl FromStr for Matcher {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use utils::parse;
 
     const TEST1: &str = "0: 1 2
 1: \"a\"
@@ -201,4 +322,57 @@ mod tests {
         println!("{:?}", matcher);
         assert_eq!(matcher.check("aab"), ("", true));
     }
+
+    #[test]
+    fn test_display_roundtrip() {
+        let matcher: Matcher = TEST1.parse().unwrap();
+        assert_eq!(
+            matcher.to_string(),
+            "0: 1 2
+1: \"a\"
+2: 3 4 | 5 6
+3: \"a\"
+4: \"b\"
+5: \"b\"
+6: \"a\"
+"
+        );
+        assert_eq!(parse::roundtrip(&matcher), Ok(()));
+        for rules in &[
+            "0: \"ab\"",
+            "0: 1 | 2 \"c\"\n1: \"a\"\n2: \"b\"",
+            "0: | 1\n1: \"a\"",
+        ] {
+            let matcher: Matcher = rules.parse().unwrap();
+            assert_eq!(parse::roundtrip(&matcher), Ok(()));
+        }
+
+        // Matchers built by hand are written as equivalent rules.
+        let matcher = check_for('a').then(check_for('b').or(check_for('c')));
+        let written: Matcher = matcher.to_string().parse().unwrap();
+        for message in &["ab", "ac", "a", "ba"] {
+            assert_eq!(written.matches(message), matcher.matches(message));
+        }
+    }
+
+    #[test]
+    fn test_multi_character_literal() {
+        let matcher: Matcher = "0: 1 2 | 2 1
+1: \"ab\"
+2: \"c\""
+            .parse()
+            .unwrap();
+        assert_eq!(matcher.matches("abc"), Ok(true));
+        assert_eq!(matcher.matches("cab"), Ok(true));
+        assert_eq!(matcher.matches("acb"), Ok(false));
+    }
+
+    #[test]
+    fn test_non_ascii() {
+        let matcher: Matcher = TEST1.parse().unwrap();
+        assert_eq!(matcher.check("äab"), ("äab", false));
+        assert!(matcher.matches("aäb").is_err());
+        assert!("0: \"ä\"".parse::<Matcher>().is_err());
+        assert!("0: \"\"".parse::<Matcher>().is_err());
+    }
 }
@@ -1,8 +1,13 @@
+use std::fmt;
 use std::str::{Chars, FromStr};
 
 use utils;
+use utils::schema::{self, Schema, Token};
 
 fn main() -> Result<(), String> {
+    if let Some(result) = schema::validate_args("day2", schema) {
+        return result;
+    }
     let (part, content) = utils::parse_args()?;
     let passwords: Vec<Password> = content
         .lines()
@@ -13,6 +18,21 @@ fn main() -> Result<(), String> {
     Ok(())
 }
 
+/// A password policy and a password on every line.
+fn schema() -> Schema {
+    use Token::*;
+
+    Schema::Lines(vec![
+        Digits,
+        Literal("-"),
+        Digits,
+        Literal(" "),
+        OneOf(schema::LOWERCASE),
+        Literal(": "),
+        Word,
+    ])
+}
+
 fn part1(numbers: Vec<Password>) -> u32 {
     numbers.iter().map(|p| p.is_valid()).filter(|o| *o).count() as u32
 }
@@ -25,7 +45,7 @@ fn part2(numbers: Vec<Password>) -> u32 {
         .count() as u32
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 struct Password {
     min: u32,
     max: u32,
@@ -81,6 +101,16 @@ impl FromStr for Password {
     }
 }
 
+impl fmt::Display for Password {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}-{} {}: {}",
+            self.min, self.max, self.character, self.password
+        )
+    }
+}
+
 fn consume_digit(chars: &mut Chars) -> Result<u32, String> {
     let num: u32 = chars
         .take_while(|c| c.is_digit(10))
@@ -89,3 +119,22 @@ fn consume_digit(chars: &mut Chars) -> Result<u32, String> {
         .or_else(|err| Err(format!("failed to parse digit: {}", err)))?;
     Ok(num)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::parse;
+
+    const TEST_STR: &str = "1-3 a: abcde
+1-3 b: cdefg
+2-9 c: ccccccccc";
+
+    #[test]
+    fn test_display_roundtrip() {
+        for l in TEST_STR.lines() {
+            let p: Password = l.parse().unwrap();
+            assert_eq!(p.to_string(), l);
+            assert_eq!(parse::roundtrip(&p), Ok(()));
+        }
+    }
+}
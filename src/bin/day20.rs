@@ -1,43 +1,565 @@
-use std::{collections::HashMap, str::FromStr};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    str::FromStr,
+};
+
+use utils::grid::{Edge, Grid, Matches, Orientation, Oriented, Pattern};
+use utils::image::{Encoder, Rgb};
+use utils::parse::{ParseError, Span};
+use utils::schema::{self, Schema, Token};
 
 fn main() -> Result<(), String> {
-    let (part, content) = utils::parse_args()?;
-    utils::run(part1, part2, part, content);
+    if let Some(result) = schema::validate_args("day20", schema) {
+        return result;
+    }
+    let (part, content, options) = utils::parse_args_with_options()?;
+    let puzzle: Puzzle = content
+        .parse()
+        .map_err(|e: ParseError| e.render(&content))?;
+    if let Some(path) = options.get::<String>("image")? {
+        let (image, found) = sea_monsters(&puzzle)?;
+        let encoder = Encoder::from_options(&options)?;
+        encoder.save(&path, &picture(&image, &found.mask), &water_color)?;
+    }
+    utils::run(part1, part2, part, puzzle);
     Ok(())
 }
 
-// TODO: read in tiles and rotate them and flip them in every direction
-fn part1(input: String) -> u32 {
-    unimplemented!()
+/// The tiles, each with its id, separated by blank lines.
+fn schema() -> Schema {
+    use Token::*;
+
+    Schema::Blocks(Box::new(Schema::Titled(
+        vec![Literal("Tile "), Digits, Literal(":")],
+        Box::new(Schema::Grid(".#")),
+    )))
+}
+
+fn part1(puzzle: Puzzle) -> Result<u64, String> {
+    let corners = puzzle.corners();
+    if corners.len() != 4 {
+        return Err(format!(
+            "expected 4 corner tiles, but found {}: {:?}",
+            corners.len(),
+            corners
+        ));
+    }
+    Ok(corners.iter().map(|id| *id as u64).product())
+}
+
+fn part2(puzzle: Puzzle) -> Result<u64, String> {
+    let (image, found) = sea_monsters(&puzzle)?;
+    Ok(roughness(&image, &found.mask) as u64)
+}
+
+const SEA_MONSTER: &str = "                  #
+#    ##    ##    ###
+ #  #  #  #  #  #";
+
+/// Assemble the image and find the sea monsters in it.
+fn sea_monsters(puzzle: &Puzzle) -> Result<(Grid<bool>, Matches), String> {
+    let arrangement = puzzle.assemble()?;
+    let image = puzzle.image(&arrangement);
+    let found = image.find(&Pattern::parse(SEA_MONSTER, ' ', |c| c == '#'));
+    if found.matches.is_empty() {
+        return Err("there are no sea monsters in the image".to_string());
+    }
+    Ok((image, found))
+}
+
+/// Count the cells of rough water that are not part of a sea monster.
+fn roughness(image: &Grid<bool>, monsters: &Grid<bool>) -> usize {
+    image
+        .cells()
+        .zip(monsters.cells())
+        .filter(|(water, monster)| **water && !**monster)
+        .count()
+}
+
+/// What a cell of the assembled image shows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Water {
+    Calm,
+    Rough,
+    Monster,
 }
 
-fn part2(_input: String) -> u32 {
-    unimplemented!()
+/// The image with the cells covered by sea monsters marked.
+fn picture(image: &Grid<bool>, monsters: &Grid<bool>) -> Grid<Water> {
+    let (height, width) = image.dimensions();
+    Grid::from_fn(height, width, |p| match (image[p], monsters[p]) {
+        (_, true) => Water::Monster,
+        (true, false) => Water::Rough,
+        (false, false) => Water::Calm,
+    })
 }
 
-#[derive(Clone, Debug)]
+fn water_color(water: &Water) -> Rgb {
+    match water {
+        Water::Calm => Rgb(12, 44, 92),
+        Water::Rough => Rgb(94, 156, 214),
+        Water::Monster => Rgb(46, 160, 67),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 struct Tile {
-    points: Vec<bool>,
-    dimensions: (u32, u32),
+    points: Grid<bool>,
+    /// The edges of the tile as it is, clockwise from the top.
+    edges: [Edge; 4],
 }
 
 impl Tile {
-    fn matches(&self, other: &Tile) {
-        let (max_x, max_y) = self.dimensions;
+    fn new(points: Grid<bool>) -> Tile {
+        let edges = points.edges();
+        Tile { points, edges }
+    }
+
+    /// View the tile rotated and flipped.
+    fn oriented(&self, orientation: Orientation) -> Oriented<'_, bool> {
+        self.points.oriented(orientation)
+    }
+
+    /// The edges of the tile in an orientation, clockwise from the top.
+    fn edges(&self, orientation: Orientation) -> [Edge; 4] {
+        orientation.edges(self.edges)
+    }
+
+    /// Check if some edge of the tile can be placed against some edge of
+    /// the other tile, after rotating and flipping them.
+    fn matches(&self, other: &Tile) -> bool {
+        self.edges
+            .iter()
+            .any(|e| other.edges.iter().any(|o| e.canonical() == o.canonical()))
+    }
+}
+
+impl FromStr for Tile {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let points = Grid::parse(s, |c| match c {
+            '#' => Some(true),
+            '.' => Some(false),
+            _ => None,
+        })?;
+        Ok(Tile::new(points))
+    }
+}
+
+impl fmt::Display for Tile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.points.map(|p| if *p { '#' } else { '.' }))
     }
 }
 
+/// A tile put into the image, rotated and flipped to fit its neighbors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Placement {
+    id: u32,
+    orientation: Orientation,
+}
+
+#[derive(Clone, Debug, PartialEq)]
 struct Puzzle {
     tiles: HashMap<u32, Tile>,
+    /// The size of the tiles, which are all square.
+    size: usize,
+}
+
+impl Puzzle {
+    /// The ids of the tiles in increasing order.
+    fn ids(&self) -> Vec<u32> {
+        let mut ids: Vec<u32> = self.tiles.keys().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// The ids of the tiles having each edge, in either direction.
+    fn edge_index(&self) -> HashMap<Edge, Vec<u32>> {
+        let mut index: HashMap<Edge, Vec<u32>> = HashMap::new();
+        for id in self.ids() {
+            for edge in self.tiles[&id].edges.iter() {
+                index.entry(edge.canonical()).or_default().push(id);
+            }
+        }
+        index
+    }
+
+    /// The tiles with two edges that no other tile has, which must be
+    /// the corners of the image.
+    fn corners(&self) -> Vec<u32> {
+        let index = self.edge_index();
+        self.ids()
+            .into_iter()
+            .filter(|id| {
+                self.tiles[id]
+                    .edges
+                    .iter()
+                    .filter(|e| index[&e.canonical()].len() == 1)
+                    .count()
+                    == 2
+            })
+            .collect()
+    }
+
+    /// Fit all tiles together into a square, row by row.
+    fn assemble(&self) -> Result<Grid<Placement>, String> {
+        let side = (1..=self.tiles.len())
+            .find(|s| s * s >= self.tiles.len())
+            .unwrap_or(0);
+        if side * side != self.tiles.len() {
+            return Err(format!(
+                "{} tiles cannot be laid out in a square",
+                self.tiles.len()
+            ));
+        }
+        if self.tiles.len() > 1 {
+            for id in self.ids() {
+                let tile = &self.tiles[&id];
+                if !self.tiles.iter().any(|(o, t)| *o != id && tile.matches(t)) {
+                    return Err(format!("tile {} does not fit against any other tile", id));
+                }
+            }
+        }
+        let mut assembly = Assembly {
+            puzzle: self,
+            index: self.edge_index(),
+            side,
+            placed: vec![],
+            used: HashSet::new(),
+        };
+        if !assembly.place() {
+            return Err("the tiles do not fit together into a square".to_string());
+        }
+        let placed = assembly.placed;
+        Ok(Grid::from_fn(side, side, |(r, c)| placed[r * side + c]))
+    }
+
+    /// Put together the image from the arranged tiles, leaving out the
+    /// borders of every tile.
+    fn image(&self, arrangement: &Grid<Placement>) -> Grid<bool> {
+        let inner = self.size - 2;
+        let (rows, columns) = arrangement.dimensions();
+        Grid::from_fn(rows * inner, columns * inner, |(r, c)| {
+            let placement = arrangement[(r / inner, c / inner)];
+            let tile = self.tiles[&placement.id].oriented(placement.orientation);
+            tile[(r % inner + 1, c % inner + 1)]
+        })
+    }
+}
+
+/// The state of a backtracking search for an arrangement of the tiles.
+struct Assembly<'a> {
+    puzzle: &'a Puzzle,
+    index: HashMap<Edge, Vec<u32>>,
+    side: usize,
+    placed: Vec<Placement>,
+    used: HashSet<u32>,
+}
+
+impl<'a> Assembly<'a> {
+    /// Try to fill the rest of the square, returning whether it worked.
+    fn place(&mut self) -> bool {
+        let n = self.placed.len();
+        if n == self.side * self.side {
+            return true;
+        }
+        let edges = |p: &Placement| self.puzzle.tiles[&p.id].edges(p.orientation);
+        let column = n % self.side;
+        let left = if column > 0 {
+            Some(edges(&self.placed[n - 1])[1])
+        } else {
+            None
+        };
+        let above = if n >= self.side {
+            Some(edges(&self.placed[n - self.side])[2])
+        } else {
+            None
+        };
+        let candidates = match left.or(above) {
+            Some(edge) => self.index[&edge.canonical()].clone(),
+            None => self.puzzle.ids(),
+        };
+        for id in candidates {
+            if self.used.contains(&id) {
+                continue;
+            }
+            for orientation in Orientation::ALL.iter() {
+                let [top, _, _, l] = self.puzzle.tiles[&id].edges(*orientation);
+                let fits = |wanted: Option<Edge>, edge| wanted.map(|e| e == edge).unwrap_or(true);
+                if !fits(left, l) || !fits(above, top) {
+                    continue;
+                }
+                self.placed.push(Placement {
+                    id,
+                    orientation: *orientation,
+                });
+                self.used.insert(id);
+                if self.place() {
+                    return true;
+                }
+                self.used.remove(&id);
+                self.placed.pop();
+            }
+        }
+        false
+    }
+}
+
+impl FromStr for Puzzle {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines: Vec<&str> = s.lines().collect();
+        let mut tiles = HashMap::new();
+        let mut size = None;
+        let mut start = 0;
+        while start < lines.len() {
+            if lines[start].trim().is_empty() {
+                start += 1;
+                continue;
+            }
+            let end = (start..lines.len())
+                .find(|i| lines[*i].trim().is_empty())
+                .unwrap_or(lines.len());
+            let header = lines[start];
+            let id = parse_header(header).map_err(|e| e.on_line(start + 1))?;
+            let body = lines[start + 1..end].join("\n");
+            let tile: Tile = body.parse().map_err(|e: ParseError| {
+                ParseError::new(e.span.on_line(e.span.line + start + 1), e.message)
+            })?;
+            let (height, width) = tile.points.dimensions();
+            let span = Span::new(start + 1, 1, header.chars().count());
+            if height != width || height < 3 {
+                return Err(ParseError::new(
+                    span,
+                    format!(
+                        "expected a square tile of at least 3 x 3, but found {} x {}",
+                        height, width
+                    ),
+                ));
+            }
+            if *size.get_or_insert(height) != height {
+                return Err(ParseError::new(
+                    span,
+                    format!(
+                        "expected a tile of size {}, but found {}",
+                        size.unwrap(),
+                        height
+                    ),
+                ));
+            }
+            if tiles.insert(id, tile).is_some() {
+                return Err(ParseError::new(span, format!("tile {} appears twice", id)));
+            }
+            start = end;
+        }
+        match size {
+            None => Err(ParseError::new(
+                Span::new(1, 1, 1),
+                "expected at least one tile",
+            )),
+            Some(size) => Ok(Puzzle { tiles, size }),
+        }
+    }
+}
+
+/// Write the tiles in increasing order of id, each under its header and
+/// separated by blank lines, as in the input.
+impl fmt::Display for Puzzle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, id) in self.ids().into_iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "Tile {}:\n{}", id, self.tiles[&id])?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse a tile header like `Tile 2311:` into the id of the tile.
+fn parse_header(line: &str) -> Result<u32, ParseError> {
+    let id = line
+        .strip_prefix("Tile ")
+        .and_then(|l| l.strip_suffix(':'))
+        .ok_or_else(|| {
+            ParseError::new(
+                Span::within(line, line),
+                "expected a header like Tile 1234:",
+            )
+        })?;
+    id.parse()
+        .map_err(|e| ParseError::new(Span::within(line, id), format!("invalid tile id: {}", e)))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use utils::parse;
 
     #[test]
     fn test_part1() {
-        unimplemented!()
+        let puzzle: Puzzle = TEST_STR.parse().unwrap();
+        assert_eq!(puzzle.tiles.len(), 9);
+        assert_eq!(puzzle.corners(), vec![1171, 1951, 2971, 3079]);
+        assert_eq!(part1(puzzle), Ok(20899048083289));
+    }
+
+    #[test]
+    fn test_part2() {
+        let puzzle: Puzzle = TEST_STR.parse().unwrap();
+        let image = puzzle.image(&puzzle.assemble().unwrap());
+        let found = image.find(&Pattern::parse(SEA_MONSTER, ' ', |c| c == '#'));
+        assert_eq!(found.matches.len(), 2);
+        assert_eq!(found.covered(), 30);
+        assert_eq!(part2(puzzle), Ok(273));
+    }
+
+    #[test]
+    fn test_picture() {
+        let puzzle: Puzzle = TEST_STR.parse().unwrap();
+        let (image, found) = sea_monsters(&puzzle).unwrap();
+        let picture = picture(&image, &found.mask);
+        let count = |w| picture.cells().filter(|c| **c == w).count();
+        assert_eq!(count(Water::Monster), 30);
+        assert_eq!(count(Water::Rough), 273);
+        assert_eq!(count(Water::Calm), 24 * 24 - 30 - 273);
+    }
+
+    #[test]
+    fn test_assemble() {
+        let puzzle: Puzzle = TEST_STR.parse().unwrap();
+        let arrangement = puzzle.assemble().unwrap();
+        assert_eq!(arrangement.dimensions(), (3, 3));
+        let corners: HashSet<u32> = [(0, 0), (0, 2), (2, 0), (2, 2)]
+            .iter()
+            .map(|p| arrangement[*p].id)
+            .collect();
+        assert_eq!(corners, puzzle.corners().into_iter().collect());
+        for ((r, c), p) in arrangement.iter() {
+            let edges = puzzle.tiles[&p.id].edges(p.orientation);
+            if c + 1 < 3 {
+                let right = arrangement[(r, c + 1)];
+                assert_eq!(
+                    edges[1],
+                    puzzle.tiles[&right.id].edges(right.orientation)[3]
+                );
+            }
+            if r + 1 < 3 {
+                let below = arrangement[(r + 1, c)];
+                assert_eq!(
+                    edges[2],
+                    puzzle.tiles[&below.id].edges(below.orientation)[0]
+                );
+            }
+        }
+
+        let image = puzzle.image(&arrangement);
+        let expected = Grid::parse(IMAGE_STR, |c| Some(c == '#')).unwrap();
+        assert!(Orientation::ALL
+            .iter()
+            .any(|o| image.oriented(*o).to_grid() == expected));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = "Tile 12:\n#.#\n.#.\n#.#\n\nTile x:\n...\n...\n..."
+            .parse::<Puzzle>()
+            .unwrap_err();
+        assert_eq!(err.span, Span::new(6, 6, 1));
+        let err = "Tile 12:\n#.#\n.x.\n#.#".parse::<Puzzle>().unwrap_err();
+        assert_eq!(err.span, Span::new(3, 2, 1));
+        let err = "Tile 12:\n#.#\n.#.\n#.#\n\nTile 12:\n...\n...\n..."
+            .parse::<Puzzle>()
+            .unwrap_err();
+        assert_eq!(err.message, "tile 12 appears twice");
+        let err = "Tile 12:\n#.#\n.#.\n\n".parse::<Puzzle>().unwrap_err();
+        assert_eq!(err.span, Span::new(1, 1, 8));
+    }
+
+    #[test]
+    fn test_inconsistent_puzzle() {
+        let mut puzzle: Puzzle = TEST_STR.parse().unwrap();
+        puzzle.tiles.remove(&2311);
+        assert_eq!(
+            puzzle.assemble(),
+            Err("8 tiles cannot be laid out in a square".to_string())
+        );
+        // Replace a tile with one that fits nowhere.
+        puzzle
+            .tiles
+            .insert(2311, Tile::new(Grid::filled(10, 10, true)));
+        assert_eq!(
+            puzzle.assemble(),
+            Err("tile 2311 does not fit against any other tile".to_string())
+        );
+        // Every tile fits against another one, but the middle tile is
+        // missing and only the edge tiles remain.
+        let mut puzzle: Puzzle = TEST_STR.parse().unwrap();
+        let arrangement = puzzle.assemble().unwrap();
+        let middle = arrangement[(1, 1)].id;
+        let corner = arrangement[(0, 0)].id;
+        let tile = puzzle.tiles[&corner].clone();
+        puzzle.tiles.remove(&middle);
+        puzzle.tiles.insert(1, tile);
+        assert_eq!(
+            puzzle.assemble(),
+            Err("the tiles do not fit together into a square".to_string())
+        );
+    }
+
+    fn tile(id: u32) -> Tile {
+        let header = format!("Tile {}:\n", id);
+        let start = TEST_STR.find(&header).unwrap() + header.len();
+        TEST_STR[start..]
+            .lines()
+            .take(10)
+            .collect::<Vec<_>>()
+            .join("\n")
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_matches() {
+        // 1951 is the top left corner of the example image, next to 2311
+        // and 2729.
+        assert!(tile(1951).matches(&tile(2311)));
+        assert!(tile(1951).matches(&tile(2729)));
+        assert!(!tile(1951).matches(&tile(3079)));
+        assert!(tile(2311).matches(&tile(3079)));
+    }
+
+    #[test]
+    fn test_orientations() {
+        let t = tile(2311);
+        for o in Orientation::ALL.iter() {
+            let view = t.oriented(*o);
+            assert_eq!(t.edges(*o), view.edges());
+            let copy = Tile::new(view.to_grid());
+            assert_eq!(copy.edges, view.edges());
+        }
+        // Flipping the top edge reverses it.
+        let flipped = t.edges(Orientation::new(0, true));
+        assert_eq!(flipped[0], t.edges[0].reversed());
+        assert_eq!(t.edges[0].bits, 0b0011010010);
+    }
+
+    #[test]
+    fn test_display_roundtrip() {
+        let puzzle: Puzzle = TEST_STR.parse().unwrap();
+        assert_eq!(parse::roundtrip(&puzzle), Ok(()));
+        // The same blocks as in the input, header and all, sorted by id.
+        let mut blocks: Vec<&str> = TEST_STR.trim_end().split("\n\n").collect();
+        blocks.sort_unstable();
+        let displayed = puzzle.to_string();
+        assert_eq!(
+            displayed.trim_end().split("\n\n").collect::<Vec<_>>(),
+            blocks
+        );
     }
 
     const TEST_STR: &str = "Tile 2311:
@@ -147,4 +669,29 @@ Tile 3079:
 ..#.###...
 ..#.......
 ..#.###...";
+
+    const IMAGE_STR: &str = ".#.#..#.##...#.##..#####
+###....#.#....#..#......
+##.##.###.#.#..######...
+###.#####...#.#####.#..#
+##.#....#.##.####...#.##
+...########.#....#####.#
+....#..#...##..#.#.###..
+.####...#..#.....#......
+#..#.##..#..###.#.##....
+#.####..#.####.#.#.###..
+###.#.#...#.######.#..##
+#.####....##..########.#
+##..##.#...#...#.#.#.#..
+...#..#..#.#.##..###.###
+.#.#....#.##.#...###.##.
+###.#...#..#.##.######..
+.#.#.###.##.##.#..#.##..
+.####.###.#...###.#..#.#
+..#.#..#..#.#.#.####.###
+#..####...#.#.#.###.###.
+#####..#####...###....##
+#.##..#..#...#..####...#
+.#.###..##..##..####.##.
+...###...##...#...#..###";
 }
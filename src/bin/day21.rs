@@ -1,6 +1,11 @@
-use std::{collections::HashMap, collections::HashSet, str::FromStr};
+use std::{collections::HashMap, collections::HashSet, fmt, str::FromStr};
+
+use utils::schema::{self, Schema, Token};
 
 fn main() -> Result<(), String> {
+    if let Some(result) = schema::validate_args("day21", schema) {
+        return result;
+    }
     let (part, content) = utils::parse_args()?;
     let lines = content
         .lines()
@@ -13,6 +18,17 @@ fn main() -> Result<(), String> {
     Ok(())
 }
 
+/// A food with its ingredients and allergens on every line.
+fn schema() -> Schema {
+    use Token::*;
+
+    Schema::Lines(vec![
+        ManyOf("abcdefghijklmnopqrstuvwxyz "),
+        Literal("(contains "),
+        Rest,
+    ])
+}
+
 fn part1(input: Vec<Line>) -> u32 {
     let mut possible_allergens: HashMap<String, Vec<String>> = HashMap::new();
     let mut determined_allergens: HashMap<String, String> = HashMap::new();
@@ -97,9 +113,20 @@ impl FromStr for Line {
     }
 }
 
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.foods.join(" "))?;
+        if !self.allergens.is_empty() {
+            write!(f, " (contains {})", self.allergens.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use utils::parse;
 
     #[test]
     fn test_parse() {
@@ -118,6 +145,15 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_display_roundtrip() {
+        for l in TEST_STR.lines() {
+            let line: Line = l.parse().unwrap();
+            assert_eq!(line.to_string(), l);
+            assert_eq!(parse::roundtrip(&line), Ok(()));
+        }
+    }
+
     #[test]
     fn test_part1() {
         let lines = TEST_STR
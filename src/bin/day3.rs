@@ -1,20 +1,47 @@
 use utils;
+use utils::grid::{Grid, Wrap};
+use utils::image::{Encoder, Rgb};
+use utils::parse::ParseError;
+use utils::schema::{self, Schema};
 
 fn main() -> Result<(), String> {
-    let (part, content) = utils::parse_args()?;
-    let map = Map::parse(content);
+    if let Some(result) = schema::validate_args("day3", schema) {
+        return result;
+    }
+    let (part, content, options) = utils::parse_args_with_options()?;
+    let map = Map::parse(content.clone()).map_err(|e| e.render(&content))?;
+    if let Some(path) = options.get::<String>("image")? {
+        let encoder = Encoder::from_options(&options)?;
+        encoder.save(&path, &map.paths(&SLOPES), &path_color)?;
+    }
     utils::run(part1, part2, part, map);
 
     Ok(())
 }
 
+/// The map of the trees.
+fn schema() -> Schema {
+    Schema::Grid(".#")
+}
+
+/// The slopes checked in part 2, as steps right and down.
+const SLOPES: [(usize, usize); 5] = [(1, 1), (3, 1), (5, 1), (7, 1), (1, 2)];
+
+/// The colors of the paths taken by each slope in an image, in order.
+const PATH_COLORS: [Rgb; 5] = [
+    Rgb(31, 119, 180),
+    Rgb(255, 127, 14),
+    Rgb(148, 103, 189),
+    Rgb(23, 190, 207),
+    Rgb(227, 119, 194),
+];
+
 fn part1(map: Map) -> u64 {
     map.trees_in_path(3, 1)
 }
 
 fn part2(map: Map) -> u64 {
-    let paths: Vec<(usize, usize)> = vec![(1, 1), (3, 1), (5, 1), (7, 1), (1, 2)];
-    paths
+    SLOPES
         .iter()
         .map(|(right, down)| map.trees_in_path(*right, *down))
         .product()
@@ -22,45 +49,130 @@ fn part2(map: Map) -> u64 {
 
 #[derive(Debug, Clone)]
 struct Map {
-    rows: Vec<Vec<char>>,
-    goal_position: (usize, usize),
+    trees: Grid<bool>,
 }
 
 impl Map {
-    fn parse(input: String) -> Map {
-        let rows: Vec<Vec<char>> = input.lines().map(|l| l.chars().collect()).collect();
-        let last_row = rows.len();
-        let last_column = rows[last_row - 1].len();
-        Map {
-            rows: rows,
-            goal_position: (last_row, last_column),
+    fn parse(input: String) -> Result<Map, ParseError> {
+        let trees = Grid::parse(&input, |c| match c {
+            '#' => Some(true),
+            '.' => Some(false),
+            _ => None,
+        })?;
+        Ok(Map { trees })
+    }
+
+    fn trees_in_path(&self, right_increment: usize, down_increment: usize) -> u64 {
+        self.trees
+            .wrapping(Wrap::Horizontal)
+            .walk((0, 0), (down_increment as i64, right_increment as i64))
+            .filter(|(_, tree)| **tree)
+            .count() as u64
+    }
+
+    /// Mark the cells visited when going down each of the slopes.
+    fn paths(&self, slopes: &[(usize, usize)]) -> Grid<Cell> {
+        let mut cells = self.trees.map(|tree| Cell {
+            tree: *tree,
+            visited_by: None,
+        });
+        for (i, (right, down)) in slopes.iter().enumerate() {
+            let path = self
+                .trees
+                .wrapping(Wrap::Horizontal)
+                .walk((0, 0), (*down as i64, *right as i64));
+            for (position, _) in path {
+                cells[position].visited_by.get_or_insert(i);
+            }
         }
+        cells
     }
+}
 
-    fn has_tree(&self, x: usize, y: usize) -> Result<bool, String> {
-        if x + 1 > self.rows.len() || y + 1 > self.rows[x].len() {
-            Err(format!(
-                "({}, {}) is outside of map (dimensions: {} x {})",
-                x,
-                y,
-                self.rows.len(),
-                self.rows[0].len()
-            ))
-        } else {
-            Ok(self.rows[x][y] == '#')
+/// A cell of the map together with the first slope visiting it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Cell {
+    tree: bool,
+    visited_by: Option<usize>,
+}
+
+/// Trees are drawn in green and open ground in white, except for trees
+/// hit on the way down, which are red, and open ground passed on the way
+/// down, which is drawn in the color of the slope.
+fn path_color(cell: &Cell) -> Rgb {
+    match (cell.tree, cell.visited_by) {
+        (true, Some(_)) => Rgb(214, 39, 40),
+        (true, None) => Rgb(44, 160, 44),
+        (false, Some(i)) => PATH_COLORS[i % PATH_COLORS.len()],
+        (false, None) => Rgb::WHITE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_STR: &str = "..##.......
+#...#...#..
+.#....#..#.
+..#.#...#.#
+.#...##..#.
+..#.##.....
+.#.#.#....#
+.#........#
+#.##...#...
+#...##....#
+.#..#...#.#";
+
+    #[test]
+    fn test_trees_in_path() {
+        let map = Map::parse(TEST_STR.to_string()).unwrap();
+        for (right, down, expected) in [(1, 1, 2), (3, 1, 7), (5, 1, 3), (7, 1, 4), (1, 2, 2)] {
+            assert_eq!(map.trees_in_path(right, down), expected);
         }
     }
 
-    fn trees_in_path(&self, right_increment: usize, down_increment: usize) -> u64 {
-        let (end_row, end_column) = self.goal_position;
-        let rows = (0..end_row)
-            .map(|r| r * down_increment)
-            .take_while(|r| *r < end_row);
-        let columns = (0..end_row)
-            .map(|c| c * right_increment % end_column)
-            .take(end_row);
-        rows.zip(columns).fold(0, |acc, (x, y)| {
-            acc + if self.has_tree(x, y).unwrap() { 1 } else { 0 }
-        })
+    #[test]
+    fn test_part_two() {
+        let map = Map::parse(TEST_STR.to_string()).unwrap();
+        assert_eq!(part2(map), 336);
+    }
+
+    #[test]
+    fn test_paths() {
+        let map = Map::parse(TEST_STR.to_string()).unwrap();
+        let cells = map.paths(&[(3, 1)]);
+        let visited: Vec<(usize, usize)> = cells
+            .iter()
+            .filter(|(_, c)| c.visited_by == Some(0))
+            .map(|(p, _)| p)
+            .collect();
+        assert_eq!(visited.len(), 11);
+        assert_eq!(&visited[..4], &[(0, 0), (1, 3), (2, 6), (3, 9)]);
+        assert_eq!(visited[4], (4, 1));
+        let hits = cells
+            .cells()
+            .filter(|c| path_color(c) == Rgb(214, 39, 40))
+            .count();
+        assert_eq!(hits as u64, map.trees_in_path(3, 1));
+    }
+
+    #[test]
+    fn test_arbitrary_slopes() {
+        let map = Map::parse(TEST_STR.to_string()).unwrap();
+        let rows: Vec<&str> = TEST_STR.lines().collect();
+        for right in 0..15 {
+            for down in 1..15 {
+                let expected = (0..rows.len())
+                    .step_by(down)
+                    .enumerate()
+                    .filter(|(i, r)| {
+                        let row = rows[*r].as_bytes();
+                        row[i * right % row.len()] == b'#'
+                    })
+                    .count() as u64;
+                assert_eq!(map.trees_in_path(right, down), expected);
+            }
+        }
     }
 }
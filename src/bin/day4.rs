@@ -1,11 +1,17 @@
 use std::char;
 use std::collections::HashMap;
+use std::fmt;
 use std::fs;
+use std::str::FromStr;
 
 use utils;
+use utils::schema::{self, Schema, Token};
 use utils::strings;
 
 fn main() -> Result<(), String> {
+    if let Some(result) = schema::validate_args("day4", schema) {
+        return result;
+    }
     let (part, content) = utils::parse_args()?;
     let passports: Vec<Passport> = strings::split_on_empty_lines(content)
         .iter()
@@ -16,6 +22,16 @@ fn main() -> Result<(), String> {
     Ok(())
 }
 
+/// Passports separated by blank lines, with their fields spread over
+/// one or more lines.
+fn schema() -> Schema {
+    use Token::*;
+
+    Schema::Blocks(Box::new(Schema::Lines(vec![ManyOf(
+        "abcdefghijklmnopqrstuvwxyz0123456789:# ",
+    )])))
+}
+
 fn part1(passports: Vec<Passport>) -> u64 {
     fn validater(p: &Passport) -> bool {
         p.byr.is_some()
@@ -82,7 +98,7 @@ fn part2(passports: Vec<Passport>) -> u64 {
         .count() as u64
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 struct Passport {
     byr: Option<String>, // (Birth Year)
     iyr: Option<String>, // (Issue Year)
@@ -125,4 +141,76 @@ impl Passport {
     fn is_valid(&self, validater: &dyn Fn(&Passport) -> bool) -> bool {
         validater(self)
     }
+
+    fn fields(&self) -> Vec<(&str, &Option<String>)> {
+        vec![
+            ("byr", &self.byr),
+            ("iyr", &self.iyr),
+            ("eyr", &self.eyr),
+            ("hgt", &self.hgt),
+            ("hcl", &self.hcl),
+            ("ecl", &self.ecl),
+            ("pid", &self.pid),
+            ("cid", &self.cid),
+        ]
+    }
+}
+
+impl FromStr for Passport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Passport::parse(s))
+    }
+}
+
+impl fmt::Display for Passport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let fields: Vec<String> = self
+            .fields()
+            .into_iter()
+            .filter_map(|(k, v)| v.as_ref().map(|v| format!("{}:{}", k, v)))
+            .collect();
+        write!(f, "{}", fields.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::parse;
+
+    const TEST_STR: &str = "ecl:gry pid:860033327 eyr:2020 hcl:#fffffd
+byr:1937 iyr:2017 cid:147 hgt:183cm
+
+iyr:2013 ecl:amb cid:350 eyr:2023 pid:028048884
+hcl:#cfa07d byr:1929
+
+hcl:#ae17e1 iyr:2013
+eyr:2024
+ecl:brn pid:760753108 byr:1931
+hgt:179cm
+
+hcl:#cfa07d eyr:2025 pid:166559648
+iyr:2011 ecl:brn hgt:59in";
+
+    fn parse_passports(s: &str) -> Vec<Passport> {
+        strings::split_on_empty_lines(s)
+            .iter()
+            .map(strings::join_lines)
+            .map(|p| Passport::parse(p.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_display_roundtrip() {
+        let passports = parse_passports(TEST_STR);
+        assert_eq!(
+            passports[3].to_string(),
+            "iyr:2011 eyr:2025 hgt:59in hcl:#cfa07d ecl:brn pid:166559648"
+        );
+        for p in passports {
+            assert_eq!(parse::roundtrip(&p), Ok(()));
+        }
+    }
 }
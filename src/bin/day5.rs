@@ -1,8 +1,12 @@
 use std::fs;
 
 use utils;
+use utils::schema::{self, Schema, Token};
 
 fn main() -> Result<(), String> {
+    if let Some(result) = schema::validate_args("day5", schema) {
+        return result;
+    }
     let (part, content) = utils::parse_args()?;
     let seats = content.lines().map(parse_seats_id).collect();
     utils::run(part1, part2, part, seats);
@@ -10,6 +14,13 @@ fn main() -> Result<(), String> {
     Ok(())
 }
 
+/// A boarding pass on every line.
+fn schema() -> Schema {
+    use Token::*;
+
+    Schema::Lines(vec![ManyOf("FB"), ManyOf("LR")])
+}
+
 fn part1(seats: Vec<u64>) -> u64 {
     seats.iter().max().map(|v| *v).expect("found no max")
 }
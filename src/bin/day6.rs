@@ -1,9 +1,13 @@
 use std::{collections::HashMap, str::FromStr};
 
 use utils;
+use utils::schema::{self, Schema, Token};
 use utils::strings;
 
 fn main() -> Result<(), String> {
+    if let Some(result) = schema::validate_args("day6", schema) {
+        return result;
+    }
     let (part, content) = utils::parse_args()?;
     let groups = strings::split_on_empty_lines(content.as_str())
         .iter()
@@ -14,6 +18,13 @@ fn main() -> Result<(), String> {
     Ok(())
 }
 
+/// The answers of every person, in groups separated by blank lines.
+fn schema() -> Schema {
+    use Token::*;
+
+    Schema::Blocks(Box::new(Schema::Lines(vec![Word])))
+}
+
 fn part1(groups: Vec<Group>) -> u32 {
     groups.iter().fold(0, |acc, g| acc + g.answers.len() as u32)
 }
@@ -1,13 +1,35 @@
 use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::str::FromStr;
 
+use utils::parse::{ParseError, Span};
+use utils::schema::{self, Schema, Token};
+
 fn main() -> Result<(), String> {
+    if let Some(result) = schema::validate_args("day7", schema) {
+        return result;
+    }
     let (part, content) = utils::parse_args()?;
-    let rules: Rules = content.parse()?;
+    let rules: Rules = content
+        .parse()
+        .map_err(|e: ParseError| e.render(&content))?;
     utils::run(part1, part2, part, rules);
     Ok(())
 }
 
+/// A rule for the contents of a bag on every line.
+fn schema() -> Schema {
+    use Token::*;
+
+    Schema::Lines(vec![
+        Word,
+        Literal(" "),
+        Word,
+        Literal(" bags contain "),
+        Rest,
+    ])
+}
+
 fn part1(r: Rules) -> u32 {
     let b: Bag = "shiny gold".parse().expect("should not fail");
     r.bags
@@ -51,7 +73,13 @@ impl FromStr for Bag {
     }
 }
 
-#[derive(Clone, Debug)]
+impl fmt::Display for Bag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.adjective, self.color)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 /// Represents all the rules for bag configurations in the luggage
 /// regulation.
 ///
@@ -76,29 +104,35 @@ impl Rules {
         )
     }
 
-    fn parse_rule_line(l: &str, rules: &mut HashMap<Bag, Vec<(Bag, u32)>>) -> Result<(), String> {
+    fn parse_rule_line(
+        l: &str,
+        rules: &mut HashMap<Bag, Vec<(Bag, u32)>>,
+    ) -> Result<(), ParseError> {
         let mut words = l.split(' ');
-        let bag: Bag = format!("{} {}", words.next().unwrap(), words.next().unwrap()).parse()?;
-        expect_str(words.next(), "bags")?;
-        expect_str(words.next(), "contain")?;
+        let bag = parse_bag(l, &mut words)?;
+        expect_str(l, words.next(), "bags")?;
+        expect_str(l, words.next(), "contain")?;
         let mut contained_bags: Vec<(Bag, u32)> = vec![];
         loop {
-            let next = words.next();
-            match next {
+            let next = match words.next() {
                 Some("no") => {
-                    expect_str(words.next(), "other")?;
-                    expect_str(words.next(), "bags.")?;
+                    expect_str(l, words.next(), "other")?;
+                    expect_str(l, words.next(), "bags.")?;
                     break;
                 }
-                Some(_) => (),
-                None => Err("expected either 'no other bags' or a number".to_string())?,
+                Some(w) => w,
+                None => Err(ParseError::new(
+                    Span::end_of(l),
+                    "expected either 'no other bags' or a number",
+                ))?,
             };
-            let amount: u32 = next
-                .unwrap()
-                .parse()
-                .map_err(|e| format!("failed to parse number: {}", e))?;
-            let contained_bag: Bag =
-                format!("{} {}", words.next().unwrap(), words.next().unwrap()).parse()?;
+            let amount: u32 = next.parse().map_err(|e| {
+                ParseError::new(
+                    Span::within(l, next),
+                    format!("failed to parse number: {}", e),
+                )
+            })?;
+            let contained_bag = parse_bag(l, &mut words)?;
             contained_bags.push((contained_bag, amount));
             if words.next().map(|w| w.contains('.')).unwrap_or(true) {
                 // last word ends with a .
@@ -111,31 +145,70 @@ impl Rules {
 }
 
 impl FromStr for Rules {
-    type Err = String;
+    type Err = ParseError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let bags: Result<HashMap<Bag, Vec<(Bag, u32)>>, String> =
-            s.lines().try_fold(HashMap::new(), |mut acc, l| {
-                match Rules::parse_rule_line(l, &mut acc) {
-                    Ok(_) => (),
-                    Err(e) => return Err(format!("failed to parse line {} as rule: {}", l, e)),
-                };
+        let bags: Result<HashMap<Bag, Vec<(Bag, u32)>>, ParseError> = s
+            .lines()
+            .enumerate()
+            .try_fold(HashMap::new(), |mut acc, (i, l)| {
+                Rules::parse_rule_line(l, &mut acc).map_err(|e| e.on_line(i + 1))?;
                 Ok(acc)
             });
         bags.map(|b| Rules { bags: b })
     }
 }
 
-fn expect_str<S>(o: Option<S>, expected: &str) -> Result<(), String>
+impl fmt::Display for Rules {
+    /// Write one rule per line, sorted by the containing bag.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut bags: Vec<&Bag> = self.bags.keys().collect();
+        bags.sort_by_key(|b| b.to_string());
+        for bag in bags {
+            let contained: Vec<String> = self.bags[bag]
+                .iter()
+                .map(|(b, n)| format!("{} {} {}", n, b, if *n == 1 { "bag" } else { "bags" }))
+                .collect();
+            if contained.is_empty() {
+                writeln!(f, "{} bags contain no other bags.", bag)?;
+            } else {
+                writeln!(f, "{} bags contain {}.", bag, contained.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse the next two words of `line` as a bag.
+fn parse_bag<'a, I>(line: &'a str, words: &mut I) -> Result<Bag, ParseError>
 where
-    S: std::string::ToString + std::fmt::Display,
+    I: Iterator<Item = &'a str>,
 {
+    let adjective = words
+        .next()
+        .ok_or_else(|| ParseError::new(Span::end_of(line), "expected an adjective"))?;
+    let color = words
+        .next()
+        .ok_or_else(|| ParseError::new(Span::end_of(line), "expected a color"))?;
+    Ok(Bag {
+        adjective: adjective.to_string(),
+        color: color.to_string(),
+    })
+}
+
+fn expect_str(line: &str, o: Option<&str>, expected: &str) -> Result<(), ParseError> {
     match o {
-        None => Err(format!("expected '{}'", expected)),
+        None => Err(ParseError::new(
+            Span::end_of(line),
+            format!("expected '{}'", expected),
+        )),
         Some(s) => {
-            if s.to_string() == expected.to_string() {
+            if s == expected {
                 Ok(())
             } else {
-                Err(format!("expected {}, but got {}", expected, s))
+                Err(ParseError::new(
+                    Span::within(line, s),
+                    format!("expected {}, but got {}", expected, s),
+                ))
             }
         }
     }
@@ -144,6 +217,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use utils::parse;
 
     const TEST_INPUT: &str = "light red bags contain 1 bright white bag, 2 muted yellow bags.
 dark orange bags contain 3 bright white bags, 4 muted yellow bags.
@@ -172,6 +246,25 @@ dotted black bags contain no other bags.";
         assert_eq!(rules.bag_can_contain_bag(&b, &b2), true);
     }
 
+    #[test]
+    fn test_parse_error_span() {
+        let err = "faded blue bags contain no other bags.
+bright white bags hold 1 shiny gold bag."
+            .parse::<Rules>()
+            .unwrap_err();
+        assert_eq!(err.span, Span::new(2, 19, 4));
+        assert_eq!(err.message, "expected contain, but got hold");
+    }
+
+    #[test]
+    fn test_display_roundtrip() {
+        let rules: Rules = TEST_INPUT.parse().expect("parsing should not fail");
+        let mut lines: Vec<&str> = TEST_INPUT.lines().collect();
+        lines.sort();
+        assert_eq!(rules.to_string(), lines.join("\n") + "\n");
+        assert_eq!(parse::roundtrip(&rules), Ok(()));
+    }
+
     #[test]
     fn test_part_one() {
         let rules: Rules = TEST_INPUT.parse().expect("parsing should not fail");
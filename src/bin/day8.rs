@@ -1,151 +1,74 @@
-use std::collections::HashSet;
+use std::fs;
 
-fn main() -> Result<(), String> {
-    let (part, content) = utils::parse_args()?;
-    let instructions: Vec<(String, i32)> = content
-        .lines()
-        .map(parse_line)
-        .collect::<Result<Vec<(String, i32)>, String>>()?;
-
-    utils::run(part1, part2, part, instructions);
-    Ok(())
-}
+use utils::parse::ParseError;
+use utils::schema::{self, Schema, Token};
+use utils::vm::{analysis, Machine, Program, RunOutcome};
 
-fn part1(instrs: Vec<(String, i32)>) -> i32 {
-    match run_instructions(instrs) {
-        Err(acc) => acc,
-        Ok(_) => panic!("part1 is not supposed to halt, it should loop infinitily."),
+fn main() -> Result<(), String> {
+    if let Some(result) = schema::validate_args("day8", schema) {
+        return result;
     }
-}
-
-fn part2(instrs: Vec<(String, i32)>) -> i32 {
-    let jmps_or_nops = &instrs
-        .iter()
-        .filter(|(o, _)| o == "nop" || o == "jmp")
-        .count();
-    for i in 0..*jmps_or_nops {
-        println!("mutating jmp or nop instruction {}", i);
-        let new_instrs = mutate(instrs.clone(), i as u32);
-        match run_instructions(new_instrs) {
-            Err(_) => continue,
-            Ok(acc) => return acc,
-        }
+    let (part, content, options) = utils::parse_args_with_options()?;
+    let program: Program = content
+        .parse()
+        .map_err(|e: ParseError| e.render(&content))?;
+    if let Some(path) = options.get::<String>("profile")? {
+        write_profile(&program, &path)?;
     }
-    panic!("could not create a instruction list that terminated");
-}
 
-fn parse_line(l: &str) -> Result<(String, i32), String> {
-    let mut words = l.split(' ');
-    let instr = words.next().map_or_else(
-        || Err("missing instruction".to_owned()),
-        |i| Ok(i.to_string()),
-    )?;
-    let operand = words.next().map_or_else(
-        || Err("missing operand".to_owned()),
-        |o| {
-            let sign = o.chars().next();
-            o.chars()
-                .skip(1)
-                .collect::<String>()
-                .parse::<i32>()
-                .map(|n| if sign == Some('-') { -n } else { n })
-                .map_err(|e| format!("failed to parse operand as string: {}", e))
-        },
-    )?;
-    Ok((instr, operand))
+    utils::run(part1, part2, part, program);
+    Ok(())
 }
 
-fn mutate(instrs: Vec<(String, i32)>, instr: u32) -> Vec<(String, i32)> {
-    let mut skipped = 0;
-    instrs
-        .iter()
-        .map(|(i, o)| {
-            let mut i = i.clone();
-            if i.as_str() == "jmp" {
-                if skipped == instr {
-                    i.clear();
-                    i.push_str("nop");
-                    println!("replaced jmp with nop");
-                }
-                skipped += 1;
-            } else if i.as_str() == "nop" {
-                if skipped == instr {
-                    i.clear();
-                    i.push_str("jmp");
-                    println!("replaced nop with jmp");
-                }
-                skipped += 1;
-            }
+/// An instruction on every line.
+fn schema() -> Schema {
+    use Token::*;
 
-            (i, *o)
-        })
-        .collect()
+    Schema::Lines(vec![Word, Literal(" "), Number])
 }
 
-#[derive(Debug, Clone)]
-struct Machine {
-    pc: usize,
-    acc: i32,
-    has_halted: bool,
+/// Run the program with tracing and write what it did to a file, as JSON
+/// if the file name ends with `.json` and as CSV otherwise.
+fn write_profile(program: &Program, path: &str) -> Result<(), String> {
+    let mut machine = Machine::new().traced();
+    machine.run(program);
+    let profile = machine.profile().ok_or("the machine was not traced")?;
+    let content = if path.ends_with(".json") {
+        profile.to_json()
+    } else {
+        profile.to_csv()
+    };
+    fs::write(path, content).map_err(|e| format!("failed to write {}: {}", path, e))
 }
 
-impl Machine {
-    fn new() -> Machine {
-        Machine {
-            pc: 0,
-            acc: 0,
-            has_halted: false,
-        }
-    }
-    fn step(&mut self, instrs: &Vec<(String, i32)>) {
-        if self.has_halted {
-            return;
-        }
-        if self.pc == instrs.len() {
-            self.has_halted = true;
-            return;
-        }
-        let (instr, o) = &instrs[self.pc];
-        match instr.as_ref() {
-            "acc" => {
-                self.acc += *o;
-                self.pc += 1
-            }
-            "nop" => self.pc += 1,
-            "jmp" => {
-                let new_pc = self.pc as i64 + *o as i64;
-                if new_pc < 0 || new_pc as usize > instrs.len() {
-                    panic!(
-                        "jmp would result in a out-of-bounds jump ({} + {} > {})",
-                        self.pc,
-                        o,
-                        instrs.len()
-                    );
-                } else {
-                    self.pc = new_pc as usize;
-                }
-            }
-            i => panic!("found unknown instruction: {} with operand {}", i, o),
-        }
+fn part1(program: Program) -> Result<i64, String> {
+    match Machine::new().run(&program) {
+        RunOutcome::InfiniteLoop { acc, .. } => Ok(acc),
+        outcome => Err(format!("expected the program to loop, but it {}", outcome)),
     }
 }
 
-fn run_instructions(instrs: Vec<(String, i32)>) -> Result<i32, i32> {
-    let mut m = Machine::new();
-    let mut executed_instrs: HashSet<usize> = HashSet::new();
-    while !executed_instrs.contains(&m.pc) {
-        executed_instrs.insert(m.pc);
-        m.step(&instrs);
-        if m.has_halted {
-            return Ok(m.acc);
-        }
-    }
-    Err(m.acc)
+fn part2(program: Program) -> Result<i64, String> {
+    analysis::repair(&program)
+        .map(|repair| repair.acc)
+        .ok_or_else(|| {
+            "no single jmp or nop can be flipped to make the program terminate".to_string()
+        })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use utils::parse;
+    use utils::random::Rng;
+    use utils::vm::{
+        analysis::Cfg,
+        asm,
+        generate::{self, Ending},
+        isa::InstructionSet,
+        mutate::{self, Edit, Strategy},
+        Instruction,
+    };
 
     const TEST_INPUT: &str = "nop +0
 acc +1
@@ -159,21 +82,296 @@ acc +6";
 
     #[test]
     fn test_part_one_test_input() {
-        let instrs: Vec<(String, i32)> = TEST_INPUT
-            .lines()
-            .map(parse_line)
-            .collect::<Result<Vec<(String, i32)>, String>>()
-            .expect("failed to parse test string");
-        assert_eq!(part1(instrs), 5);
+        let program: Program = TEST_INPUT.parse().expect("failed to parse test string");
+        assert_eq!(part1(program), Ok(5));
     }
 
     #[test]
     fn test_part_two_test_input() {
-        let instrs: Vec<(String, i32)> = TEST_INPUT
-            .lines()
-            .map(parse_line)
-            .collect::<Result<Vec<(String, i32)>, String>>()
-            .expect("failed to parse test string");
-        assert_eq!(part2(instrs), 8);
+        let program: Program = TEST_INPUT.parse().expect("failed to parse test string");
+        assert_eq!(part2(program), Ok(8));
+    }
+
+    /// Flip the `instr`th `jmp` or `nop` of the program.
+    fn mutate(program: &Program, instr: u32) -> Program {
+        let mut skipped = 0;
+        let instructions = program
+            .iter()
+            .map(|i| match *i {
+                Instruction::Jmp(n) => {
+                    skipped += 1;
+                    if skipped - 1 == instr {
+                        Instruction::Nop(n)
+                    } else {
+                        *i
+                    }
+                }
+                Instruction::Nop(n) => {
+                    skipped += 1;
+                    if skipped - 1 == instr {
+                        Instruction::Jmp(n)
+                    } else {
+                        *i
+                    }
+                }
+                Instruction::Acc(_) => *i,
+            })
+            .collect();
+        Program::new(instructions)
+    }
+
+    /// The accumulator after flipping the first `jmp` or `nop` that makes
+    /// the program end, found by trying each of them in turn.
+    fn brute_force(program: &Program) -> Option<i64> {
+        let jmps_or_nops = program
+            .iter()
+            .filter(|i| matches!(i, Instruction::Jmp(_) | Instruction::Nop(_)))
+            .count();
+        (0..jmps_or_nops).find_map(|i| match Machine::new().run(&mutate(program, i as u32)) {
+            RunOutcome::Terminated { acc } => Some(acc),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_mutate() {
+        let program: Program = TEST_INPUT.parse().unwrap();
+        let mutated = mutate(&program, 1);
+        assert_eq!(mutated[2], Instruction::Nop(4));
+        assert_eq!(mutated.len(), program.len());
+    }
+
+    #[test]
+    fn test_repair_matches_brute_force() {
+        let program: Program = TEST_INPUT.parse().unwrap();
+        let repair = analysis::repair(&program);
+        assert_eq!(repair.map(|r| r.pc), Some(7));
+        assert_eq!(repair.map(|r| r.acc), brute_force(&program));
+        assert_eq!(repair, analysis::repair_brute_force(&program));
+    }
+
+    #[test]
+    fn test_part_two_without_repair() {
+        let program: Program = "jmp +0\njmp +0".parse().unwrap();
+        assert!(part2(program).is_err());
+    }
+
+    #[test]
+    fn test_display_roundtrip() {
+        for l in TEST_INPUT.lines() {
+            let instr: Instruction = l.parse().unwrap();
+            assert_eq!(instr.to_string(), l);
+            assert_eq!(parse::roundtrip(&instr), Ok(()));
+        }
+        let program: Program = TEST_INPUT.parse().unwrap();
+        assert_eq!(parse::roundtrip(&program), Ok(()));
+    }
+
+    #[test]
+    fn test_part_one_errors() {
+        let program: Program = "acc +1\njmp +1".parse().unwrap();
+        assert_eq!(
+            part1(program),
+            Err("expected the program to loop, but it terminated with acc 1".to_string())
+        );
+        let program: Program = "acc +1\njmp +3".parse().unwrap();
+        assert_eq!(
+            part1(program),
+            Err(
+                "expected the program to loop, but it stopped because the jmp at 1 jumps outside of the program, to 4"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_assembly_roundtrip() {
+        let program: Program = TEST_INPUT.parse().unwrap();
+        let source = asm::disassemble(&program);
+        assert_eq!(asm::assemble(&source), Ok(program.clone()));
+        assert_eq!(asm::disassemble(&asm::assemble(&source).unwrap()), source);
+        assert!(source.contains("jmp L"));
+
+        let repaired = analysis::repair(&program).unwrap().apply(&program);
+        let source = asm::disassemble(&repaired);
+        assert_eq!(asm::assemble(&source), Ok(repaired));
+    }
+
+    #[test]
+    fn test_write_profile() {
+        let program: Program = TEST_INPUT.parse().unwrap();
+        let dir = std::env::temp_dir();
+        let name = format!("day8-test-profile-{}", std::process::id());
+        let csv_path = dir.join(format!("{}.csv", name));
+        let json_path = dir.join(format!("{}.json", name));
+        write_profile(&program, csv_path.to_str().unwrap()).unwrap();
+        write_profile(&program, json_path.to_str().unwrap()).unwrap();
+        let csv = fs::read_to_string(&csv_path).unwrap();
+        let json = fs::read_to_string(&json_path).unwrap();
+        fs::remove_file(csv_path).unwrap();
+        fs::remove_file(json_path).unwrap();
+        // The trace ends right before running instruction 1 again.
+        assert_eq!(csv.lines().count(), 1 + 7);
+        assert_eq!(csv.lines().last(), Some("6,4,jmp -3,5,5,1"));
+        assert!(json.ends_with(r#""first_revisit":{"step":7,"pc":1}}"#));
+    }
+
+    #[test]
+    fn test_unknown_opcode() {
+        let err = "nop +0\nmul +2".parse::<Program>().unwrap_err();
+        assert_eq!(err.span.line, 2);
+        assert_eq!(err.message, "unknown opcode mul, expected acc, jmp or nop");
+    }
+
+    #[test]
+    fn test_default_instruction_set() {
+        let set = InstructionSet::default();
+        let program: Program = TEST_INPUT.parse().unwrap();
+        let repaired = analysis::repair(&program).unwrap().apply(&program);
+        for program in &[program, repaired] {
+            let code = set.parse(&program.to_string()).unwrap();
+            let mut machine = Machine::for_set(&set);
+            assert_eq!(machine.run(&code), Machine::new().run(program));
+            assert_eq!(machine.state().registers().count(), 1);
+        }
+        let err = set.parse("nop +0\nmul +2").unwrap_err();
+        assert_eq!(err.span.line, 2);
+        assert_eq!(err.message, "unknown opcode mul, expected acc, jmp or nop");
+    }
+
+    #[test]
+    fn test_mutation_search_matches_repair() {
+        let program: Program = TEST_INPUT.parse().unwrap();
+        let repair = analysis::repair(&program).unwrap();
+        let found = mutate::search(
+            &program,
+            &[Strategy::Swap(Instruction::Jmp, Instruction::Nop)],
+        );
+        assert_eq!(found.len(), 1);
+        let (mutation, acc) = found[0];
+        assert_eq!(mutation.pc, repair.pc);
+        assert_eq!(mutation.edit, Edit::Replace(repair.instruction));
+        assert_eq!(acc, repair.acc);
+
+        let all = [
+            Strategy::Swap(Instruction::Jmp, Instruction::Nop),
+            Strategy::Swap(Instruction::Acc, Instruction::Nop),
+            Strategy::NegateOperand,
+            Strategy::Delete,
+        ];
+        let found = mutate::search(&program, &all);
+        for threads in 1..=3 {
+            assert_eq!(mutate::search_parallel(&program, &all, threads), found);
+        }
+        for (mutation, acc) in found {
+            assert_eq!(
+                Machine::new().run(&mutation.apply(&program)),
+                RunOutcome::Terminated { acc }
+            );
+        }
+    }
+
+    /// The number of random programs each property is checked against.
+    const CASES: u64 = 200;
+
+    /// Random programs of both endings and many sizes, the same ones on
+    /// every run.
+    fn random_programs() -> impl Iterator<Item = (Ending, Program)> {
+        (0..CASES).map(|seed| {
+            let mut rng = Rng::new(seed);
+            let ending = if seed % 2 == 0 {
+                Ending::Terminates
+            } else {
+                Ending::Loops
+            };
+            let size = 1 + rng.below(40);
+            (ending, generate::program(&mut rng, size, ending))
+        })
+    }
+
+    #[test]
+    fn test_generated_programs_end_as_asked() {
+        for (ending, program) in random_programs() {
+            let terminated = matches!(Machine::new().run(&program), RunOutcome::Terminated { .. });
+            assert_eq!(terminated, ending == Ending::Terminates, "{}", program);
+        }
+    }
+
+    #[test]
+    fn test_reachability_agrees_with_execution() {
+        for (_, program) in random_programs() {
+            let cfg = Cfg::new(&program);
+            let reaching = cfg.reaching_end();
+            for (pc, reaches) in reaching.iter().enumerate().take(program.len()) {
+                // Jumping over a first instruction starts the program at pc,
+                // as every offset after it stays the same.
+                let mut instructions = vec![Instruction::Jmp(pc as i32 + 1)];
+                instructions.extend_from_slice(program.instructions());
+                let started = Program::new(instructions);
+                let terminated =
+                    matches!(Machine::new().run(&started), RunOutcome::Terminated { .. });
+                assert_eq!(terminated, *reaches, "pc {} of\n{}", pc, program);
+            }
+
+            let mut machine = Machine::new().traced();
+            machine.run(&program);
+            let run: Vec<usize> = machine
+                .profile()
+                .unwrap()
+                .trace()
+                .iter()
+                .map(|s| s.pc)
+                .collect();
+            assert_eq!(cfg.executed_path(), run, "{}", program);
+        }
+    }
+
+    #[test]
+    fn test_repair_agrees_with_brute_force() {
+        let mut repaired = 0;
+        // Programs that already end need no repair.
+        let looping = random_programs().filter(|(ending, _)| *ending == Ending::Loops);
+        for (_, program) in looping {
+            let fast = analysis::repair(&program);
+            let slow = analysis::repair_brute_force(&program);
+            assert_eq!(slow.map(|r| r.acc), brute_force(&program), "{}", program);
+            let swaps = mutate::search(
+                &program,
+                &[Strategy::Swap(Instruction::Jmp, Instruction::Nop)],
+            );
+            assert_eq!(fast.is_some(), slow.is_some(), "{}", program);
+            if let Some(r) = fast {
+                assert!(
+                    swaps.contains(&(
+                        mutate::Mutation {
+                            pc: r.pc,
+                            edit: Edit::Replace(r.instruction),
+                        },
+                        r.acc
+                    )),
+                    "{}",
+                    program
+                );
+                repaired += 1;
+            }
+            if swaps.len() == 1 {
+                assert_eq!(fast, slow, "{}", program);
+            }
+        }
+        // Make sure the property is not only checked on programs without
+        // a repair.
+        assert!(
+            repaired > CASES / 4,
+            "only {} programs had a repair",
+            repaired
+        );
+    }
+
+    #[test]
+    fn test_random_assembly_roundtrip() {
+        for (_, program) in random_programs() {
+            let source = asm::disassemble(&program);
+            assert_eq!(asm::assemble(&source), Ok(program.clone()), "{}", source);
+        }
     }
 }
@@ -1,19 +1,26 @@
+use utils::parse::{self, ParseError, Separator};
+use utils::schema::{self, Schema, Token};
+
 fn main() -> Result<(), String> {
+    if let Some(result) = schema::validate_args("day9", schema) {
+        return result;
+    }
     let (part, content) = utils::parse_args()?;
-    let input = parse_lines(&content)?;
+    let input = parse_lines(&content).map_err(|e| e.render(&content))?;
     utils::run(part1, part2, part, input);
     Ok(())
 }
 
-fn parse_lines(s: &str) -> Result<Vec<i64>, String> {
-    let numbers: Vec<i64> = s
-        .lines()
-        .map(|l| {
-            l.parse::<i64>()
-                .map_err(|e| format!("failed to parse {} as a number: {}", l, e))
-        })
-        .collect::<Result<Vec<i64>, String>>()?;
-    Ok(numbers)
+/// The XMAS data: the preamble of 25 numbers, then at least one number
+/// to check.
+fn schema() -> Schema {
+    use Token::*;
+
+    Schema::counted(26.., Schema::Lines(vec![Digits]))
+}
+
+fn parse_lines(s: &str) -> Result<Vec<i64>, ParseError> {
+    parse::numbers(s, Separator::Newline)
 }
 
 fn part1(input: Vec<i64>) -> Result<i64, String> {
@@ -95,4 +102,17 @@ mod tests {
         let nums = parse_lines(TEST_INPUT).unwrap();
         assert_eq!(encryption_weakness(&nums, 5), Some(62));
     }
+
+    #[test]
+    fn test_schema() {
+        for count in &[26, 1000, 1500] {
+            let input: Vec<String> = (1..=*count).map(|n| n.to_string()).collect();
+            assert_eq!(schema().validate(&input.join("\n")), Ok(()));
+        }
+        let errors = schema().validate(TEST_INPUT).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "expected at least 26 lines, but found 20"
+        );
+    }
 }
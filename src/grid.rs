@@ -0,0 +1,330 @@
+use std::{
+    fmt,
+    ops::{Index, IndexMut},
+};
+
+use crate::parse::{ParseError, Span};
+
+mod neighbors;
+mod orientation;
+mod pattern;
+mod wrapping;
+
+pub use neighbors::{Visibility, MOORE, VON_NEUMANN};
+pub use orientation::{Edge, Orientation, Oriented, Side};
+pub use pattern::{Match, Matches, Pattern};
+pub use wrapping::{Walk, Wrap, WrappingGrid};
+
+/// A rectangular grid of cells stored row by row in one contiguous
+/// vector.
+///
+/// Positions are given as `(row, column)`, with `(0, 0)` in the top
+/// left corner.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Grid<T> {
+    cells: Vec<T>,
+    width: usize,
+    height: usize,
+}
+
+impl<T> Grid<T> {
+    /// Create a grid where every cell is given by calling `f` with its
+    /// position.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::grid::Grid;
+    ///
+    /// let g = Grid::from_fn(2, 3, |(r, c)| r * 10 + c);
+    /// assert_eq!(g[(1, 2)], 12);
+    /// assert_eq!(g.dimensions(), (2, 3));
+    /// ```
+    pub fn from_fn<F>(height: usize, width: usize, mut f: F) -> Grid<T>
+    where
+        F: FnMut((usize, usize)) -> T,
+    {
+        let mut cells = Vec::with_capacity(width * height);
+        for row in 0..height {
+            for column in 0..width {
+                cells.push(f((row, column)));
+            }
+        }
+        Grid {
+            cells,
+            width,
+            height,
+        }
+    }
+
+    /// Create a grid with `height` rows and `width` columns where every
+    /// cell has the same value.
+    pub fn filled(height: usize, width: usize, value: T) -> Grid<T>
+    where
+        T: Clone,
+    {
+        Grid {
+            cells: vec![value; width * height],
+            width,
+            height,
+        }
+    }
+
+    /// Parse a map of characters with one row per line, turning each
+    /// character into a cell with `cell`.
+    ///
+    /// Fails if `cell` does not accept a character or if the rows have
+    /// different lengths.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::grid::Grid;
+    /// use utils::parse::Span;
+    ///
+    /// let trees = |c| match c {
+    ///     '#' => Some(true),
+    ///     '.' => Some(false),
+    ///     _ => None,
+    /// };
+    /// let g = Grid::parse("..#\n#..", trees).unwrap();
+    /// assert_eq!(g.dimensions(), (2, 3));
+    /// assert_eq!(g[(0, 2)], true);
+    ///
+    /// assert_eq!(Grid::parse("..#\n#x.", trees).unwrap_err().span, Span::new(2, 2, 1));
+    /// assert_eq!(Grid::parse("..#\n#.", trees).unwrap_err().span, Span::new(2, 1, 2));
+    /// ```
+    pub fn parse<F>(input: &str, cell: F) -> Result<Grid<T>, ParseError>
+    where
+        F: Fn(char) -> Option<T>,
+    {
+        let mut cells = vec![];
+        let mut width = None;
+        let mut height = 0;
+        for (row, l) in input.lines().enumerate() {
+            let mut len = 0;
+            for (column, c) in l.chars().enumerate() {
+                let value = cell(c).ok_or_else(|| {
+                    ParseError::new(
+                        Span::new(row + 1, column + 1, 1),
+                        format!("unexpected character {:?}", c),
+                    )
+                })?;
+                cells.push(value);
+                len += 1;
+            }
+            let width = *width.get_or_insert(len);
+            if len != width {
+                return Err(ParseError::new(
+                    Span::new(row + 1, 1, len.max(1)),
+                    format!("expected a row of width {}, but found {}", width, len),
+                ));
+            }
+            height += 1;
+        }
+        Ok(Grid {
+            cells,
+            width: width.unwrap_or(0),
+            height,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The number of rows and columns in the grid.
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.height, self.width)
+    }
+
+    /// Check if the position is inside the grid.
+    pub fn contains(&self, (row, column): (usize, usize)) -> bool {
+        row < self.height && column < self.width
+    }
+
+    /// Get the cell at a position, or `None` if it is outside the grid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::grid::Grid;
+    ///
+    /// let g = Grid::from_fn(2, 2, |(r, c)| r + c);
+    /// assert_eq!(g.get((1, 1)), Some(&2));
+    /// assert_eq!(g.get((2, 0)), None);
+    /// ```
+    pub fn get(&self, position: (usize, usize)) -> Option<&T> {
+        if self.contains(position) {
+            Some(&self.cells[self.index(position)])
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self, position: (usize, usize)) -> Option<&mut T> {
+        if self.contains(position) {
+            let i = self.index(position);
+            Some(&mut self.cells[i])
+        } else {
+            None
+        }
+    }
+
+    /// Get the cell at a position that may be negative, or `None` if it
+    /// is outside the grid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::grid::Grid;
+    ///
+    /// let g = Grid::from_fn(2, 2, |(r, c)| r + c);
+    /// assert_eq!(g.get_signed((0, 1)), Some(&1));
+    /// assert_eq!(g.get_signed((-1, 1)), None);
+    /// ```
+    pub fn get_signed(&self, (row, column): (i64, i64)) -> Option<&T> {
+        if row < 0 || column < 0 {
+            None
+        } else {
+            self.get((row as usize, column as usize))
+        }
+    }
+
+    /// Iterate over the rows of the grid, from top to bottom.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::grid::Grid;
+    ///
+    /// let g = Grid::from_fn(2, 3, |(r, c)| r * 3 + c);
+    /// let rows: Vec<&[usize]> = g.rows().collect();
+    /// assert_eq!(rows, vec![&[0, 1, 2][..], &[3, 4, 5][..]]);
+    /// ```
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        // `chunks` panics on a zero width, which only empty grids have.
+        self.cells.chunks(self.width.max(1))
+    }
+
+    pub fn row(&self, row: usize) -> &[T] {
+        &self.cells[row * self.width..(row + 1) * self.width]
+    }
+
+    /// Iterate over the cells of a column, from top to bottom.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `column` is not less than the width of the grid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::grid::Grid;
+    ///
+    /// let g = Grid::from_fn(2, 3, |(r, c)| r * 3 + c);
+    /// assert_eq!(g.column(1).collect::<Vec<_>>(), vec![&1, &4]);
+    /// assert_eq!(g.columns().count(), 3);
+    /// ```
+    ///
+    /// ```should_panic
+    /// use utils::grid::Grid;
+    ///
+    /// let g = Grid::from_fn(2, 3, |(r, c)| r * 3 + c);
+    /// g.column(3);
+    /// ```
+    pub fn column(&self, column: usize) -> impl Iterator<Item = &T> {
+        assert!(
+            column < self.width,
+            "column {} is outside of a grid of width {}",
+            column,
+            self.width
+        );
+        self.cells[column..].iter().step_by(self.width)
+    }
+
+    pub fn columns(&self) -> impl Iterator<Item = impl Iterator<Item = &T>> {
+        (0..self.width).map(move |c| self.column(c))
+    }
+
+    /// Iterate over all cells together with their positions, row by row.
+    pub fn iter(&self) -> impl Iterator<Item = ((usize, usize), &T)> {
+        let width = self.width;
+        self.cells
+            .iter()
+            .enumerate()
+            .map(move |(i, c)| ((i / width, i % width), c))
+    }
+
+    /// Iterate over all cells, row by row.
+    pub fn cells(&self) -> impl Iterator<Item = &T> {
+        self.cells.iter()
+    }
+
+    /// Create a grid of the same size by applying `f` to every cell.
+    pub fn map<U, F>(&self, f: F) -> Grid<U>
+    where
+        F: FnMut(&T) -> U,
+    {
+        Grid {
+            cells: self.cells.iter().map(f).collect(),
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    fn index(&self, (row, column): (usize, usize)) -> usize {
+        row * self.width + column
+    }
+}
+
+impl<T> Index<(usize, usize)> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, position: (usize, usize)) -> &T {
+        self.get(position).unwrap_or_else(|| {
+            panic!(
+                "{:?} is outside of grid (dimensions: {} x {})",
+                position, self.height, self.width
+            )
+        })
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Grid<T> {
+    fn index_mut(&mut self, position: (usize, usize)) -> &mut T {
+        let (height, width) = self.dimensions();
+        self.get_mut(position).unwrap_or_else(|| {
+            panic!(
+                "{:?} is outside of grid (dimensions: {} x {})",
+                position, height, width
+            )
+        })
+    }
+}
+
+/// Write the grid with one line per row.
+///
+/// # Examples
+///
+/// ```
+/// use utils::grid::Grid;
+///
+/// let g = Grid::parse("#.\n.#", |c| Some(c)).unwrap();
+/// assert_eq!(g.to_string(), "#.\n.#\n");
+/// ```
+impl<T: fmt::Display> fmt::Display for Grid<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in self.rows() {
+            for cell in row {
+                write!(f, "{}", cell)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,224 @@
+use super::Grid;
+
+/// Offsets to the eight cells surrounding a cell, clockwise from north.
+pub const MOORE: [(i64, i64); 8] = [
+    (-1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+];
+
+/// Offsets to the four cells sharing an edge with a cell, clockwise from
+/// north.
+pub const VON_NEUMANN: [(i64, i64); 4] = [(-1, 0), (0, 1), (1, 0), (0, -1)];
+
+impl<T> Grid<T> {
+    /// Move from a position by an offset, or `None` if that leaves the
+    /// grid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::grid::Grid;
+    ///
+    /// let g = Grid::filled(3, 3, 0);
+    /// assert_eq!(g.offset((1, 1), (1, -1)), Some((2, 0)));
+    /// assert_eq!(g.offset((0, 1), (-1, 0)), None);
+    /// ```
+    pub fn offset(
+        &self,
+        (row, column): (usize, usize),
+        (dr, dc): (i64, i64),
+    ) -> Option<(usize, usize)> {
+        let (r, c) = (row as i64 + dr, column as i64 + dc);
+        if r < 0 || c < 0 || !self.contains((r as usize, c as usize)) {
+            None
+        } else {
+            Some((r as usize, c as usize))
+        }
+    }
+
+    /// Iterate over the cells at the given offsets from a position,
+    /// skipping those outside the grid.
+    pub fn neighbors<'a>(
+        &'a self,
+        position: (usize, usize),
+        offsets: &'a [(i64, i64)],
+    ) -> impl Iterator<Item = ((usize, usize), &'a T)> + 'a {
+        offsets
+            .iter()
+            .filter_map(move |o| self.offset(position, *o))
+            .map(move |p| (p, &self[p]))
+    }
+
+    /// Iterate over the up to eight cells surrounding a position.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::grid::Grid;
+    ///
+    /// let g = Grid::from_fn(3, 3, |(r, c)| r * 3 + c);
+    /// assert_eq!(g.moore((1, 1)).map(|(_, v)| *v).sum::<usize>(), 36 - 4);
+    /// assert_eq!(g.moore((0, 0)).count(), 3);
+    /// ```
+    pub fn moore(
+        &self,
+        position: (usize, usize),
+    ) -> impl Iterator<Item = ((usize, usize), &T)> + '_ {
+        self.neighbors(position, &MOORE)
+    }
+
+    /// Iterate over the up to four cells sharing an edge with a position.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::grid::Grid;
+    ///
+    /// let g = Grid::from_fn(3, 3, |(r, c)| r * 3 + c);
+    /// let values: Vec<usize> = g.von_neumann((1, 1)).map(|(_, v)| *v).collect();
+    /// assert_eq!(values, vec![1, 5, 7, 3]);
+    /// assert_eq!(g.von_neumann((2, 2)).count(), 2);
+    /// ```
+    pub fn von_neumann(
+        &self,
+        position: (usize, usize),
+    ) -> impl Iterator<Item = ((usize, usize), &T)> + '_ {
+        self.neighbors(position, &VON_NEUMANN)
+    }
+
+    /// Find the first cell seen when looking from a position in a
+    /// direction, looking past every cell for which `skip` is true.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::grid::Grid;
+    ///
+    /// let g = Grid::parse("L..L.", |c| Some(c)).unwrap();
+    /// assert_eq!(g.first_visible((0, 0), (0, 1), |c| *c == '.'), Some((0, 3)));
+    /// assert_eq!(g.first_visible((0, 3), (0, 1), |c| *c == '.'), None);
+    /// ```
+    pub fn first_visible<P>(
+        &self,
+        position: (usize, usize),
+        direction: (i64, i64),
+        skip: P,
+    ) -> Option<(usize, usize)>
+    where
+        P: Fn(&T) -> bool,
+    {
+        self.first_visible_within(position, direction, skip, usize::MAX)
+    }
+
+    /// Like [`Grid::first_visible`], but only looking at most `range`
+    /// steps away.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::grid::Grid;
+    ///
+    /// let g = Grid::parse("L..L.", |c| Some(c)).unwrap();
+    /// assert_eq!(g.first_visible_within((0, 0), (0, 1), |c| *c == '.', 3), Some((0, 3)));
+    /// assert_eq!(g.first_visible_within((0, 0), (0, 1), |c| *c == '.', 2), None);
+    /// ```
+    pub fn first_visible_within<P>(
+        &self,
+        position: (usize, usize),
+        direction: (i64, i64),
+        skip: P,
+        range: usize,
+    ) -> Option<(usize, usize)>
+    where
+        P: Fn(&T) -> bool,
+    {
+        let mut p = self.offset(position, direction)?;
+        for _ in 1..range {
+            if !skip(&self[p]) {
+                return Some(p);
+            }
+            p = self.offset(p, direction)?;
+        }
+        if range > 0 && !skip(&self[p]) {
+            Some(p)
+        } else {
+            None
+        }
+    }
+
+    /// Iterate over the first cell seen in each of the eight directions
+    /// from a position, looking past every cell for which `skip` is true.
+    pub fn visible<'a, P>(
+        &'a self,
+        position: (usize, usize),
+        skip: P,
+    ) -> impl Iterator<Item = ((usize, usize), &'a T)> + 'a
+    where
+        P: Fn(&T) -> bool + 'a,
+    {
+        MOORE
+            .iter()
+            .filter_map(move |d| self.first_visible(position, *d, &skip))
+            .map(move |p| (p, &self[p]))
+    }
+}
+
+/// A precomputed table of the cells visible from every cell of a grid,
+/// for when the cells looked past never change.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Visibility {
+    visible: Grid<[Option<(usize, usize)>; 8]>,
+}
+
+impl Visibility {
+    /// Look in all eight directions from every cell of `grid`, looking
+    /// past every cell for which `skip` is true.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::grid::{Grid, Visibility};
+    ///
+    /// let g = Grid::parse("L.L\n...\nL.L", |c| Some(c)).unwrap();
+    /// let v = Visibility::new(&g, |c| *c == '.');
+    /// assert_eq!(v.visible((0, 0)).collect::<Vec<_>>(), vec![(0, 2), (2, 2), (2, 0)]);
+    /// assert_eq!(
+    ///     v.visible((0, 0)).collect::<Vec<_>>(),
+    ///     g.visible((0, 0), |c| *c == '.').map(|(p, _)| p).collect::<Vec<_>>()
+    /// );
+    /// ```
+    pub fn new<T, P>(grid: &Grid<T>, skip: P) -> Visibility
+    where
+        P: Fn(&T) -> bool,
+    {
+        Visibility::within(grid, skip, usize::MAX)
+    }
+
+    /// Like [`Visibility::new`], but only looking at most `range` steps
+    /// away.
+    pub fn within<T, P>(grid: &Grid<T>, skip: P, range: usize) -> Visibility
+    where
+        P: Fn(&T) -> bool,
+    {
+        let (height, width) = grid.dimensions();
+        let visible = Grid::from_fn(height, width, |p| {
+            let mut visible = [None; 8];
+            for (v, d) in visible.iter_mut().zip(MOORE.iter()) {
+                *v = grid.first_visible_within(p, *d, &skip, range);
+            }
+            visible
+        });
+        Visibility { visible }
+    }
+
+    /// Iterate over the positions visible from a position.
+    pub fn visible(&self, position: (usize, usize)) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.visible[position].iter().filter_map(|p| *p)
+    }
+}
@@ -0,0 +1,303 @@
+use std::ops::Index;
+
+use super::Grid;
+
+/// One of the eight ways to rotate and flip a grid.
+///
+/// The grid is first mirrored left to right if `flipped` is set, and then
+/// rotated clockwise by a quarter turn `rotations` times.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Orientation {
+    pub rotations: u8,
+    pub flipped: bool,
+}
+
+impl Orientation {
+    /// The grid as it is.
+    pub const IDENTITY: Orientation = Orientation {
+        rotations: 0,
+        flipped: false,
+    };
+
+    /// Every orientation, starting with the identity.
+    pub const ALL: [Orientation; 8] = [
+        Orientation::new(0, false),
+        Orientation::new(1, false),
+        Orientation::new(2, false),
+        Orientation::new(3, false),
+        Orientation::new(0, true),
+        Orientation::new(1, true),
+        Orientation::new(2, true),
+        Orientation::new(3, true),
+    ];
+
+    pub const fn new(rotations: u8, flipped: bool) -> Orientation {
+        Orientation {
+            rotations: rotations % 4,
+            flipped,
+        }
+    }
+
+    /// The dimensions of a grid with the given dimensions when seen in
+    /// this orientation.
+    pub fn dimensions(self, (height, width): (usize, usize)) -> (usize, usize) {
+        match self.rotations {
+            0 | 2 => (height, width),
+            _ => (width, height),
+        }
+    }
+
+    /// Find the edges of a grid seen in this orientation from its edges
+    /// as it is, without looking at the grid again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::grid::{Grid, Orientation};
+    ///
+    /// let g = Grid::parse("##.\n..#\n#..", |c| Some(c == '#')).unwrap();
+    /// for o in Orientation::ALL.iter() {
+    ///     assert_eq!(o.edges(g.edges()), g.oriented(*o).edges());
+    /// }
+    /// ```
+    pub fn edges(self, [top, right, bottom, left]: [Edge; 4]) -> [Edge; 4] {
+        let mut edges = if self.flipped {
+            [top.reversed(), left, bottom.reversed(), right]
+        } else {
+            [top, right, bottom, left]
+        };
+        for _ in 0..self.rotations {
+            let [top, right, bottom, left] = edges;
+            edges = [left.reversed(), top, right.reversed(), bottom];
+        }
+        edges
+    }
+}
+
+/// A side of a grid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Side {
+    Top,
+    Right,
+    Bottom,
+    Left,
+}
+
+impl Side {
+    /// Every side, clockwise from the top.
+    pub const ALL: [Side; 4] = [Side::Top, Side::Right, Side::Bottom, Side::Left];
+
+    /// The side facing this side on a neighboring grid.
+    pub fn opposite(self) -> Side {
+        match self {
+            Side::Top => Side::Bottom,
+            Side::Right => Side::Left,
+            Side::Bottom => Side::Top,
+            Side::Left => Side::Right,
+        }
+    }
+}
+
+/// The cells along a side of a grid of booleans packed into bits, read
+/// from left to right or from top to bottom with the first cell as the
+/// most significant bit.
+///
+/// Reading edges this way makes the right edge of a grid equal to the
+/// left edge of the grid to its right, and the bottom edge equal to the
+/// top edge of the grid below it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Edge {
+    pub bits: u64,
+    pub len: u8,
+}
+
+impl Edge {
+    /// Pack the cells of an edge, at most 64 of them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::grid::Edge;
+    ///
+    /// let e = Edge::from_cells(vec![true, true, false, false]);
+    /// assert_eq!(e.bits, 0b1100);
+    /// assert_eq!(e.reversed().bits, 0b0011);
+    /// assert_eq!(e.canonical(), e.reversed().canonical());
+    /// ```
+    pub fn from_cells<I>(cells: I) -> Edge
+    where
+        I: IntoIterator<Item = bool>,
+    {
+        let mut edge = Edge { bits: 0, len: 0 };
+        for cell in cells {
+            assert!(edge.len < 64, "edges can be at most 64 cells long");
+            edge.bits = edge.bits << 1 | cell as u64;
+            edge.len += 1;
+        }
+        edge
+    }
+
+    /// The same edge read in the other direction.
+    pub fn reversed(self) -> Edge {
+        let bits = if self.len == 0 {
+            0
+        } else {
+            self.bits.reverse_bits() >> (64 - self.len as u32)
+        };
+        Edge {
+            bits,
+            len: self.len,
+        }
+    }
+
+    /// The same value for an edge and its reverse, for comparing edges
+    /// regardless of how the grids they belong to are flipped.
+    pub fn canonical(self) -> Edge {
+        let reversed = self.reversed();
+        if reversed.bits < self.bits {
+            reversed
+        } else {
+            self
+        }
+    }
+}
+
+impl<T> Grid<T> {
+    /// View the grid rotated and flipped, without copying it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::grid::{Grid, Orientation};
+    ///
+    /// let g = Grid::parse("ab\ncd\nef", |c| Some(c)).unwrap();
+    /// let rotated = g.oriented(Orientation::new(1, false));
+    /// assert_eq!(rotated.dimensions(), (2, 3));
+    /// assert_eq!(rotated.to_grid().to_string(), "eca\nfdb\n");
+    /// let flipped = g.oriented(Orientation::new(0, true));
+    /// assert_eq!(flipped.to_grid().to_string(), "ba\ndc\nfe\n");
+    /// assert_eq!(flipped[(2, 0)], 'f');
+    /// ```
+    pub fn oriented(&self, orientation: Orientation) -> Oriented<'_, T> {
+        Oriented {
+            grid: self,
+            orientation,
+        }
+    }
+}
+
+impl Grid<bool> {
+    /// The edges of the grid, clockwise from the top.
+    pub fn edges(&self) -> [Edge; 4] {
+        self.oriented(Orientation::IDENTITY).edges()
+    }
+}
+
+/// A view of a grid in some orientation.
+#[derive(Debug)]
+pub struct Oriented<'a, T> {
+    grid: &'a Grid<T>,
+    orientation: Orientation,
+}
+
+impl<'a, T> Clone for Oriented<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for Oriented<'a, T> {}
+
+impl<'a, T> Oriented<'a, T> {
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    /// The number of rows and columns in the view.
+    pub fn dimensions(&self) -> (usize, usize) {
+        self.orientation.dimensions(self.grid.dimensions())
+    }
+
+    /// Get the cell at a position in the view, or `None` if it is outside
+    /// the grid.
+    pub fn get(&self, (row, column): (usize, usize)) -> Option<&'a T> {
+        let (height, width) = self.dimensions();
+        if row >= height || column >= width {
+            return None;
+        }
+        let grid = self.grid;
+        grid.get(self.source((row, column)))
+    }
+
+    /// Iterate over all cells of the view together with their positions,
+    /// row by row.
+    pub fn iter(&self) -> impl Iterator<Item = ((usize, usize), &'a T)> {
+        let view = *self;
+        let (height, width) = self.dimensions();
+        (0..height)
+            .flat_map(move |r| (0..width).map(move |c| (r, c)))
+            .map(move |p| (p, view.get(p).unwrap()))
+    }
+
+    /// Copy the view into a new grid.
+    pub fn to_grid(&self) -> Grid<T>
+    where
+        T: Clone,
+    {
+        let (height, width) = self.dimensions();
+        Grid::from_fn(height, width, |p| self[p].clone())
+    }
+
+    /// The position in the underlying grid of a position in the view.
+    fn source(&self, (mut row, mut column): (usize, usize)) -> (usize, usize) {
+        let (height, width) = self.grid.dimensions();
+        for turn in (0..self.orientation.rotations).rev() {
+            // Undo one clockwise quarter turn of a grid with `rows` rows.
+            let rows = Orientation::new(turn, false).dimensions((height, width)).0;
+            let previous = (rows - 1 - column, row);
+            row = previous.0;
+            column = previous.1;
+        }
+        if self.orientation.flipped {
+            column = width - 1 - column;
+        }
+        (row, column)
+    }
+}
+
+impl<'a> Oriented<'a, bool> {
+    /// The edge on one side of the view.
+    pub fn edge(&self, side: Side) -> Edge {
+        let (height, width) = self.dimensions();
+        match side {
+            Side::Top => Edge::from_cells((0..width).map(|c| self[(0, c)])),
+            Side::Right => Edge::from_cells((0..height).map(|r| self[(r, width - 1)])),
+            Side::Bottom => Edge::from_cells((0..width).map(|c| self[(height - 1, c)])),
+            Side::Left => Edge::from_cells((0..height).map(|r| self[(r, 0)])),
+        }
+    }
+
+    /// The edges of the view, clockwise from the top.
+    pub fn edges(&self) -> [Edge; 4] {
+        [
+            self.edge(Side::Top),
+            self.edge(Side::Right),
+            self.edge(Side::Bottom),
+            self.edge(Side::Left),
+        ]
+    }
+}
+
+impl<'a, T> Index<(usize, usize)> for Oriented<'a, T> {
+    type Output = T;
+
+    fn index(&self, position: (usize, usize)) -> &T {
+        let (height, width) = self.dimensions();
+        self.get(position).unwrap_or_else(|| {
+            panic!(
+                "{:?} is outside of grid (dimensions: {} x {})",
+                position, height, width
+            )
+        })
+    }
+}
@@ -0,0 +1,182 @@
+use super::{Grid, Orientation};
+
+/// A rectangle of cells to look for in a grid, where wildcard cells
+/// match anything.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pattern<T> {
+    cells: Grid<Option<T>>,
+}
+
+impl<T> Pattern<T> {
+    /// Create a pattern from a grid where `None` is a wildcard.
+    pub fn new(cells: Grid<Option<T>>) -> Pattern<T> {
+        Pattern { cells }
+    }
+
+    /// Parse a pattern with one row per line, where `wildcard` matches
+    /// anything and every other character becomes a cell with `cell`.
+    ///
+    /// Rows shorter than the longest row are filled up with wildcards, so
+    /// trailing wildcards may be left out.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::grid::Pattern;
+    ///
+    /// let p = Pattern::parse(" #\n###", ' ', |c| c == '#');
+    /// assert_eq!(p.dimensions(), (2, 3));
+    /// assert_eq!(p.len(), 4);
+    /// ```
+    pub fn parse<F>(input: &str, wildcard: char, cell: F) -> Pattern<T>
+    where
+        F: Fn(char) -> T,
+    {
+        let rows: Vec<Vec<char>> = input.lines().map(|l| l.chars().collect()).collect();
+        let width = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+        let cells = Grid::from_fn(rows.len(), width, |(r, c)| {
+            rows[r].get(c).filter(|c| **c != wildcard).map(|c| cell(*c))
+        });
+        Pattern { cells }
+    }
+
+    /// The number of rows and columns in the pattern.
+    pub fn dimensions(&self) -> (usize, usize) {
+        self.cells.dimensions()
+    }
+
+    /// The number of cells in the pattern that are not wildcards.
+    pub fn len(&self) -> usize {
+        self.cells.cells().filter(|c| c.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The pattern rotated and flipped.
+    pub fn oriented(&self, orientation: Orientation) -> Pattern<T>
+    where
+        T: Clone,
+    {
+        Pattern {
+            cells: self.cells.oriented(orientation).to_grid(),
+        }
+    }
+
+    /// The positions of the cells that are not wildcards, relative to
+    /// the top left corner of the pattern.
+    fn required(&self) -> Vec<((usize, usize), &T)> {
+        self.cells
+            .iter()
+            .filter_map(|(p, c)| c.as_ref().map(|c| (p, c)))
+            .collect()
+    }
+}
+
+/// A place where a pattern was found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Match {
+    /// The position of the top left corner of the oriented pattern.
+    pub position: (usize, usize),
+    pub orientation: Orientation,
+}
+
+/// Every place a pattern was found in a grid.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Matches {
+    pub matches: Vec<Match>,
+    /// The cells of the grid covered by a non-wildcard cell of some
+    /// match. Cells covered by overlapping matches are only marked once.
+    pub mask: Grid<bool>,
+}
+
+impl Matches {
+    /// The number of cells covered by some match.
+    pub fn covered(&self) -> usize {
+        self.mask.cells().filter(|c| **c).count()
+    }
+}
+
+impl<T: PartialEq> Grid<T> {
+    /// Find the top left corners of every place the pattern matches the
+    /// grid as it is, without rotating or flipping it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::grid::{Grid, Pattern};
+    ///
+    /// let g = Grid::parse("abab\nbaba", |c| Some(c)).unwrap();
+    /// let p = Pattern::parse("a?\n?a", '?', |c| c);
+    /// assert_eq!(g.positions_of(&p), vec![(0, 0), (0, 2)]);
+    /// ```
+    pub fn positions_of(&self, pattern: &Pattern<T>) -> Vec<(usize, usize)> {
+        let required = pattern.required();
+        let (height, width) = self.dimensions();
+        let (rows, columns) = pattern.dimensions();
+        if rows > height || columns > width {
+            return vec![];
+        }
+        let mut positions = vec![];
+        for row in 0..=height - rows {
+            for column in 0..=width - columns {
+                if required
+                    .iter()
+                    .all(|((r, c), cell)| self[(row + r, column + c)] == **cell)
+                {
+                    positions.push((row, column));
+                }
+            }
+        }
+        positions
+    }
+
+    /// Find every place the pattern matches the grid in any of its eight
+    /// orientations.
+    ///
+    /// Orientations that turn the pattern into one already searched for,
+    /// like rotating a symmetric pattern, are skipped, so every place is
+    /// only found once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::grid::{Grid, Orientation, Pattern};
+    ///
+    /// let g = Grid::parse("#..\n##.\n.##", |c| Some(c == '#')).unwrap();
+    /// let p = Pattern::parse("#\n##", '.', |c| c == '#');
+    /// let found = g.find(&p);
+    /// // Twice as it is, and once rotated half a turn, overlapping both.
+    /// assert_eq!(found.matches.len(), 3);
+    /// assert_eq!(found.matches[0].orientation, Orientation::IDENTITY);
+    /// let mask = found.mask.map(|m| if *m { '#' } else { '.' });
+    /// assert_eq!(mask.to_string(), "#..\n##.\n.##\n");
+    /// assert_eq!(found.covered(), 5);
+    /// ```
+    pub fn find(&self, pattern: &Pattern<T>) -> Matches
+    where
+        T: Clone,
+    {
+        let mut searched: Vec<Pattern<T>> = vec![];
+        let mut matches = vec![];
+        let mut mask = Grid::filled(self.height(), self.width(), false);
+        for orientation in Orientation::ALL.iter() {
+            let oriented = pattern.oriented(*orientation);
+            if searched.contains(&oriented) {
+                continue;
+            }
+            for position in self.positions_of(&oriented) {
+                for ((r, c), _) in oriented.required() {
+                    mask[(position.0 + r, position.1 + c)] = true;
+                }
+                matches.push(Match {
+                    position,
+                    orientation: *orientation,
+                });
+            }
+            searched.push(oriented);
+        }
+        Matches { matches, mask }
+    }
+}
@@ -0,0 +1,141 @@
+use super::Grid;
+
+/// The axes along which a grid repeats itself.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wrap {
+    /// Leaving on the right comes back on the left, and vice versa.
+    Horizontal,
+    /// Leaving at the bottom comes back at the top, and vice versa.
+    Vertical,
+    /// The grid repeats in all directions.
+    Both,
+}
+
+/// A view of a grid that repeats itself along one or both axes.
+#[derive(Debug)]
+pub struct WrappingGrid<'a, T> {
+    grid: &'a Grid<T>,
+    wrap: Wrap,
+}
+
+impl<T> Grid<T> {
+    /// View the grid as repeating itself along the axes given by `wrap`.
+    pub fn wrapping(&self, wrap: Wrap) -> WrappingGrid<'_, T> {
+        WrappingGrid { grid: self, wrap }
+    }
+}
+
+impl<'a, T> WrappingGrid<'a, T> {
+    /// Find the position in the underlying grid for a position that may
+    /// lie outside of it, or `None` if it is outside along an axis that
+    /// does not wrap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::grid::{Grid, Wrap};
+    ///
+    /// let g = Grid::filled(2, 3, '.');
+    /// let w = g.wrapping(Wrap::Horizontal);
+    /// assert_eq!(w.position((1, 7)), Some((1, 1)));
+    /// assert_eq!(w.position((0, -1)), Some((0, 2)));
+    /// assert_eq!(w.position((2, 0)), None);
+    /// assert_eq!(g.wrapping(Wrap::Both).position((-3, -3)), Some((1, 0)));
+    /// ```
+    pub fn position(&self, (row, column): (i64, i64)) -> Option<(usize, usize)> {
+        let (height, width) = self.grid.dimensions();
+        let (wrap_rows, wrap_columns) = match self.wrap {
+            Wrap::Horizontal => (false, true),
+            Wrap::Vertical => (true, false),
+            Wrap::Both => (true, true),
+        };
+        let row = wrap(row, height, wrap_rows)?;
+        let column = wrap(column, width, wrap_columns)?;
+        Some((row, column))
+    }
+
+    /// Get the cell at a position that may lie outside the underlying
+    /// grid.
+    pub fn get(&self, position: (i64, i64)) -> Option<&'a T> {
+        let grid = self.grid;
+        self.position(position).map(|p| &grid[p])
+    }
+
+    /// Walk from `start` by adding `step` to the position over and over,
+    /// yielding every cell stepped on, starting with `start` itself.
+    ///
+    /// The walk ends when it leaves the grid along an axis that does not
+    /// wrap, so a walk over a grid wrapping both ways never ends.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `step` is 0 along an axis that does not wrap, since the
+    /// walk would then never leave the grid.
+    ///
+    /// ```should_panic
+    /// use utils::grid::{Grid, Wrap};
+    ///
+    /// let g = Grid::filled(2, 3, '.');
+    /// g.wrapping(Wrap::Horizontal).walk((0, 0), (0, 1));
+    /// ```
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::grid::{Grid, Wrap};
+    ///
+    /// let g = Grid::from_fn(5, 3, |(r, c)| r * 3 + c);
+    /// let cells: Vec<usize> = g
+    ///     .wrapping(Wrap::Horizontal)
+    ///     .walk((0, 0), (2, 2))
+    ///     .map(|(_, v)| *v)
+    ///     .collect();
+    /// assert_eq!(cells, vec![0, 8, 13]);
+    /// ```
+    pub fn walk(&self, start: (usize, usize), step: (i64, i64)) -> Walk<'a, T> {
+        let (rows, columns) = step;
+        match self.wrap {
+            Wrap::Horizontal => assert!(rows != 0, "a walk must move between rows"),
+            Wrap::Vertical => assert!(columns != 0, "a walk must move between columns"),
+            Wrap::Both => {}
+        }
+        Walk {
+            grid: WrappingGrid {
+                grid: self.grid,
+                wrap: self.wrap,
+            },
+            position: (start.0 as i64, start.1 as i64),
+            step,
+        }
+    }
+}
+
+fn wrap(i: i64, len: usize, wraps: bool) -> Option<usize> {
+    if wraps && len > 0 {
+        Some(i.rem_euclid(len as i64) as usize)
+    } else if i >= 0 && (i as usize) < len {
+        Some(i as usize)
+    } else {
+        None
+    }
+}
+
+/// An iterator over the cells stepped on when walking a wrapping grid,
+/// created by [`WrappingGrid::walk`].
+#[derive(Debug)]
+pub struct Walk<'a, T> {
+    grid: WrappingGrid<'a, T>,
+    position: (i64, i64),
+    step: (i64, i64),
+}
+
+impl<'a, T> Iterator for Walk<'a, T> {
+    type Item = ((usize, usize), &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let p = self.grid.position(self.position)?;
+        let (row, column) = self.position;
+        self.position = (row + self.step.0, column + self.step.1);
+        Some((p, &self.grid.grid[p]))
+    }
+}
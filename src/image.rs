@@ -0,0 +1,260 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use crate::{grid::Grid, Options};
+
+/// A color with 8 bits per channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb(0, 0, 0);
+    pub const WHITE: Rgb = Rgb(255, 255, 255);
+
+    /// A shade of gray.
+    pub fn gray(level: u8) -> Rgb {
+        Rgb(level, level, level)
+    }
+
+    /// The perceived brightness of the color, using the Rec. 601 weights.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::image::Rgb;
+    ///
+    /// assert_eq!(Rgb::WHITE.luma(), 255);
+    /// assert_eq!(Rgb::gray(17).luma(), 17);
+    /// assert!(Rgb(0, 255, 0).luma() > Rgb(255, 0, 0).luma());
+    /// ```
+    pub fn luma(self) -> u8 {
+        let Rgb(r, g, b) = self;
+        ((299 * r as u32 + 587 * g as u32 + 114 * b as u32 + 500) / 1000) as u8
+    }
+}
+
+/// Decides the color of each cell in an image of a grid.
+pub trait Palette<T> {
+    fn color(&self, cell: &T) -> Rgb;
+}
+
+impl<T, F> Palette<T> for F
+where
+    F: Fn(&T) -> Rgb,
+{
+    fn color(&self, cell: &T) -> Rgb {
+        self(cell)
+    }
+}
+
+/// Black for `true` and white for `false`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Monochrome;
+
+impl Palette<bool> for Monochrome {
+    fn color(&self, cell: &bool) -> Rgb {
+        if *cell {
+            Rgb::BLACK
+        } else {
+            Rgb::WHITE
+        }
+    }
+}
+
+/// The kinds of binary Netpbm images.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Binary PPM (`P6`), in color.
+    Ppm,
+    /// Binary PGM (`P5`), in shades of gray.
+    Pgm,
+}
+
+impl Format {
+    /// The file extension images in the format usually have.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Ppm => "ppm",
+            Format::Pgm => "pgm",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ppm" => Ok(Format::Ppm),
+            "pgm" => Ok(Format::Pgm),
+            _ => Err(format!("unknown image format {}, expected ppm or pgm", s)),
+        }
+    }
+}
+
+/// Writes grids as images, drawing every cell as a square of
+/// `scale` × `scale` pixels.
+///
+/// # Examples
+///
+/// ```
+/// use utils::grid::Grid;
+/// use utils::image::{Encoder, Format, Monochrome};
+///
+/// let g = Grid::parse("#.", |c| Some(c == '#')).unwrap();
+/// let mut out = vec![];
+/// Encoder::new(Format::Pgm).write(&mut out, &g, &Monochrome).unwrap();
+/// assert_eq!(out, b"P5\n2 1\n255\n\x00\xff");
+///
+/// let mut out = vec![];
+/// Encoder::new(Format::Ppm).scale(2).write(&mut out, &g, &Monochrome).unwrap();
+/// assert_eq!(out.len(), "P6\n4 2\n255\n".len() + 4 * 2 * 3);
+/// assert!(out.starts_with(b"P6\n4 2\n255\n\x00\x00\x00\x00\x00\x00\xff"));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Encoder {
+    format: Format,
+    scale: usize,
+}
+
+impl Encoder {
+    pub fn new(format: Format) -> Encoder {
+        Encoder { format, scale: 1 }
+    }
+
+    /// Set up an encoder from the `--format` (`ppm` by default) and
+    /// `--scale` (1 by default) command line options.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::image::{Encoder, Format};
+    /// use utils::Options;
+    ///
+    /// let options = Options::parse(&["--format", "pgm", "--scale", "3"]).unwrap();
+    /// assert_eq!(Encoder::from_options(&options), Ok(Encoder::new(Format::Pgm).scale(3)));
+    /// assert_eq!(Encoder::from_options(&Options::default()), Ok(Encoder::new(Format::Ppm)));
+    /// ```
+    pub fn from_options(options: &Options) -> Result<Encoder, String> {
+        let format = options.get_or("format", Format::Ppm)?;
+        Ok(Encoder::new(format).scale(options.get_or("scale", 1)?))
+    }
+
+    /// Draw every cell as a square with sides of `scale` pixels.
+    pub fn scale(self, scale: usize) -> Encoder {
+        Encoder {
+            scale: scale.max(1),
+            ..self
+        }
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    pub fn write<W, T, P>(&self, out: &mut W, grid: &Grid<T>, palette: &P) -> io::Result<()>
+    where
+        W: Write,
+        P: Palette<T> + ?Sized,
+    {
+        let magic = match self.format {
+            Format::Ppm => "P6",
+            Format::Pgm => "P5",
+        };
+        let (height, width) = grid.dimensions();
+        write!(
+            out,
+            "{}\n{} {}\n255\n",
+            magic,
+            width * self.scale,
+            height * self.scale
+        )?;
+        let mut line = Vec::with_capacity(width * self.scale * 3);
+        for row in grid.rows() {
+            line.clear();
+            for cell in row {
+                let color = palette.color(cell);
+                for _ in 0..self.scale {
+                    match self.format {
+                        Format::Ppm => line.extend(&[color.0, color.1, color.2]),
+                        Format::Pgm => line.push(color.luma()),
+                    }
+                }
+            }
+            for _ in 0..self.scale {
+                out.write_all(&line)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the image to a new file at `path`.
+    pub fn save<T, P>(&self, path: &str, grid: &Grid<T>, palette: &P) -> Result<(), String>
+    where
+        P: Palette<T> + ?Sized,
+    {
+        let file = File::create(path).map_err(|e| format!("failed to create {}: {}", path, e))?;
+        let mut out = BufWriter::new(file);
+        self.write(&mut out, grid, palette)
+            .and_then(|_| out.flush())
+            .map_err(|e| format!("failed to write {}: {}", path, e))
+    }
+}
+
+/// Writes the generations of a simulation to numbered image files named
+/// like `prefix0000.ppm`, `prefix0001.ppm` and so on.
+///
+/// # Examples
+///
+/// ```
+/// use utils::grid::Grid;
+/// use utils::image::{Encoder, Format, Frames, Monochrome};
+///
+/// let prefix = std::env::temp_dir().join("utils-frames-doctest-");
+/// let mut frames = Frames::new(prefix.to_str().unwrap(), Encoder::new(Format::Pgm));
+/// let first = frames.frame(&Grid::filled(1, 1, true), &Monochrome).unwrap();
+/// let second = frames.frame(&Grid::filled(1, 1, false), &Monochrome).unwrap();
+/// assert!(first.to_str().unwrap().ends_with("utils-frames-doctest-0000.pgm"));
+/// assert_eq!(std::fs::read(&second).unwrap(), b"P5\n1 1\n255\n\xff");
+/// # std::fs::remove_file(first).unwrap();
+/// # std::fs::remove_file(second).unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct Frames {
+    prefix: String,
+    encoder: Encoder,
+    next: usize,
+}
+
+impl Frames {
+    pub fn new(prefix: &str, encoder: Encoder) -> Frames {
+        Frames {
+            prefix: prefix.to_string(),
+            encoder,
+            next: 0,
+        }
+    }
+
+    /// Write the grid to the next file in the sequence, returning its
+    /// path.
+    pub fn frame<T, P>(&mut self, grid: &Grid<T>, palette: &P) -> io::Result<PathBuf>
+    where
+        P: Palette<T> + ?Sized,
+    {
+        let path = PathBuf::from(format!(
+            "{}{:04}.{}",
+            self.prefix,
+            self.next,
+            self.encoder.format().extension()
+        ));
+        let mut out = BufWriter::new(File::create(&path)?);
+        self.encoder.write(&mut out, grid, palette)?;
+        out.flush()?;
+        self.next += 1;
+        Ok(path)
+    }
+}
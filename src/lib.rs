@@ -1,6 +1,20 @@
-use std::{clone::Clone, fmt::Debug, fs, str::FromStr};
+use std::{
+    clone::Clone,
+    collections::HashMap,
+    fmt::{Debug, Display},
+    fs,
+    str::FromStr,
+};
 
+pub mod animate;
+pub mod automaton;
+pub mod grid;
+pub mod image;
+pub mod parse;
+pub mod random;
+pub mod schema;
 pub mod strings;
+pub mod vm;
 
 #[derive(Debug, PartialEq)]
 pub enum Part {
@@ -42,6 +56,104 @@ pub fn parse_args() -> Result<(Part, String), String> {
     Ok((part, content))
 }
 
+/// Options given on the command line, either as `--name value` or as a
+/// bare `--flag`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Options {
+    values: HashMap<String, Option<String>>,
+}
+
+impl Options {
+    /// Parse options from arguments of the form `--name value` or
+    /// `--flag`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::Options;
+    ///
+    /// let args = ["--tolerance", "5", "--verbose"];
+    /// let options = Options::parse(&args).unwrap();
+    /// assert_eq!(options.get::<u32>("tolerance"), Ok(Some(5)));
+    /// assert_eq!(options.get::<u32>("radius"), Ok(None));
+    /// assert_eq!(options.get_or("radius", 1), Ok(1));
+    /// assert!(options.flag("verbose"));
+    /// assert!(!options.flag("quiet"));
+    /// assert!(options.get::<u32>("verbose").is_err());
+    /// assert!(Options::parse(&["5"]).is_err());
+    /// ```
+    pub fn parse<S: AsRef<str>>(args: &[S]) -> Result<Options, String> {
+        let mut values = HashMap::new();
+        let mut args = args.iter().map(|a| a.as_ref()).peekable();
+        while let Some(arg) = args.next() {
+            let name = arg
+                .strip_prefix("--")
+                .ok_or_else(|| format!("expected an option starting with --, found {}", arg))?;
+            let value = match args.peek() {
+                Some(v) if !v.starts_with("--") => args.next().map(|v| v.to_string()),
+                _ => None,
+            };
+            values.insert(name.to_string(), value);
+        }
+        Ok(Options { values })
+    }
+
+    /// Check if an option was given, with or without a value.
+    pub fn flag(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    /// Get the value of an option, or `None` if it was not given.
+    pub fn get<T>(&self, name: &str) -> Result<Option<T>, String>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match self.values.get(name) {
+            None => Ok(None),
+            Some(None) => Err(format!("expected a value for --{}", name)),
+            Some(Some(v)) => v
+                .parse()
+                .map(Some)
+                .map_err(|e| format!("failed to read --{} {}: {}", name, v, e)),
+        }
+    }
+
+    /// Get the value of an option, or `default` if it was not given.
+    pub fn get_or<T>(&self, name: &str, default: T) -> Result<T, String>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.get(name).map(|v| v.unwrap_or(default))
+    }
+}
+
+/// Parse arguments for a advent of code problem that takes options.
+///
+/// Returns the part to be run, the content of the INPUT_FILE and the
+/// options given between them.
+///
+/// Assumptions:
+/// Usage: day --part <1|2|both> [--option [value]]... INPUT_FILE
+pub fn parse_args_with_options() -> Result<(Part, String, Options), String> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 4 || args[1] != "--part" {
+        Err(format!(
+            "Usage: {} --part <1|2|both> [--option [value]]... <input>",
+            args[0]
+        ))?
+    }
+    let part: Part = args[2]
+        .parse::<Part>()
+        .map_err(|err| format!("failed to read part: {}", err))?;
+    let options = Options::parse(&args[3..args.len() - 1])?;
+    let input_file = &args[args.len() - 1];
+    let content =
+        fs::read_to_string(input_file).map_err(|err| format!("failed to read input: {}", err))?;
+    Ok((part, content, options))
+}
+
 /// Run the part functions, depending on what part is provided.
 pub fn run<In: Clone, Out: Debug>(
     part1: fn(In) -> Out,
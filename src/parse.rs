@@ -0,0 +1,274 @@
+use std::fmt;
+
+/// A location in the input, given as a 1-based line and column together
+/// with the number of characters it covers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub len: usize,
+}
+
+impl Span {
+    pub fn new(line: usize, column: usize, len: usize) -> Span {
+        Span { line, column, len }
+    }
+
+    /// Create a span on line 1 covering `part`, which must be a slice
+    /// of `line`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::parse::Span;
+    ///
+    /// let line = "jmp +4";
+    /// let operand = line.split(' ').nth(1).unwrap();
+    /// assert_eq!(Span::within(line, operand), Span::new(1, 5, 2));
+    /// ```
+    pub fn within(line: &str, part: &str) -> Span {
+        let offset = (part.as_ptr() as usize)
+            .checked_sub(line.as_ptr() as usize)
+            .filter(|o| *o + part.len() <= line.len())
+            .expect("part must be a slice of line");
+        Span {
+            line: 1,
+            column: line[..offset].chars().count() + 1,
+            len: part.chars().count(),
+        }
+    }
+
+    /// Create a span on line 1 pointing just past the end of `line`, for
+    /// reporting input that ended too early.
+    pub fn end_of(line: &str) -> Span {
+        Span {
+            line: 1,
+            column: line.chars().count() + 1,
+            len: 1,
+        }
+    }
+
+    /// Move the span to another line, keeping its column and length.
+    pub fn on_line(self, line: usize) -> Span {
+        Span { line, ..self }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// An error found while parsing some input, pointing at the offending
+/// part of it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new<S: ToString>(span: Span, message: S) -> ParseError {
+        ParseError {
+            span,
+            message: message.to_string(),
+        }
+    }
+
+    /// Move the error to another line, for errors produced by parsers that
+    /// only see a single line of the input.
+    pub fn on_line(self, line: usize) -> ParseError {
+        ParseError {
+            span: self.span.on_line(line),
+            ..self
+        }
+    }
+
+    /// Render the error together with the offending line of `source`,
+    /// underlining the span with carets.
+    ///
+    /// Spans are 1-based, but the fields are public, so a span at line or
+    /// column 0 is rendered with an empty line and carets from the start
+    /// instead of panicking.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::parse::{ParseError, Span};
+    ///
+    /// let source = "F10\nX3\nR90";
+    /// let err = ParseError::new(Span::new(2, 1, 1), "unknown direction");
+    /// assert_eq!(
+    ///     err.render(source),
+    ///     "error: unknown direction
+    ///  --> 2:1
+    ///   |
+    /// 2 | X3
+    ///   | ^"
+    /// );
+    ///
+    /// let err = ParseError::new(Span::new(0, 0, 2), "no line");
+    /// assert_eq!(err.render(source), "error: no line\n --> 0:0\n  |\n0 | \n  | ^^");
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        let line = self
+            .span
+            .line
+            .checked_sub(1)
+            .and_then(|i| source.lines().nth(i))
+            .unwrap_or("");
+        let number = self.span.line.to_string();
+        let gutter = " ".repeat(number.len());
+        format!(
+            "error: {}\n{}--> {}\n{} |\n{} | {}\n{} | {}{}",
+            self.message,
+            gutter,
+            self.span,
+            gutter,
+            number,
+            line,
+            gutter,
+            " ".repeat(self.span.column.saturating_sub(1)),
+            "^".repeat(self.span.len.max(1))
+        )
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.message)
+    }
+}
+
+impl From<ParseError> for String {
+    fn from(e: ParseError) -> String {
+        e.to_string()
+    }
+}
+
+/// Parse every line of `input` with `parse`, moving any error to the
+/// line it was found on.
+///
+/// # Examples
+///
+/// ```
+/// use utils::parse::{self, ParseError, Span};
+///
+/// let parse_digit = |l: &str| {
+///     l.parse::<u8>()
+///         .map_err(|e| ParseError::new(Span::within(l, l), e))
+/// };
+/// assert_eq!(parse::lines("1\n2", parse_digit), Ok(vec![1, 2]));
+/// assert_eq!(
+///     parse::lines("1\nx", parse_digit).unwrap_err().span,
+///     Span::new(2, 1, 1)
+/// );
+/// ```
+pub fn lines<T, F>(input: &str, parse: F) -> Result<Vec<T>, ParseError>
+where
+    F: Fn(&str) -> Result<T, ParseError>,
+{
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, l)| parse(l).map_err(|e| e.on_line(i + 1)))
+        .collect()
+}
+
+/// How the numbers in a list are separated from each other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Separator {
+    /// One number per line.
+    Newline,
+    /// Numbers separated by commas, possibly spread over several lines.
+    Comma,
+    /// Numbers separated by any amount of whitespace.
+    Whitespace,
+}
+
+/// Parse a list of numbers separated by `separator`.
+///
+/// Blank lines are skipped and every token is trimmed before it is
+/// parsed. The error for a token that is not a number, or that does not
+/// fit in `T`, points at the token and tells its index in the list.
+///
+/// # Examples
+///
+/// ```
+/// use utils::parse::{self, Separator, Span};
+///
+/// assert_eq!(parse::numbers::<i32>("1\n-2\n+3\n", Separator::Newline), Ok(vec![1, -2, 3]));
+/// assert_eq!(parse::numbers::<u32>("0,3,6", Separator::Comma), Ok(vec![0, 3, 6]));
+/// assert_eq!(parse::numbers::<u8>(" 1  2\n3", Separator::Whitespace), Ok(vec![1, 2, 3]));
+///
+/// let err = parse::numbers::<u8>("1,2,300", Separator::Comma).unwrap_err();
+/// assert_eq!(err.span, Span::new(1, 5, 3));
+/// assert_eq!(
+///     err.message,
+///     "failed to parse 300 (at index 2) as a number: number too large to fit in target type"
+/// );
+/// ```
+pub fn numbers<T>(input: &str, separator: Separator) -> Result<Vec<T>, ParseError>
+where
+    T: std::str::FromStr,
+    T::Err: fmt::Display,
+{
+    let mut numbers = vec![];
+    for (i, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let tokens: Vec<&str> = match separator {
+            Separator::Newline => vec![line.trim()],
+            Separator::Comma => line.split(',').map(str::trim).collect(),
+            Separator::Whitespace => line.split_whitespace().collect(),
+        };
+        for token in tokens {
+            let n = token.parse::<T>().map_err(|e| {
+                ParseError::new(
+                    Span::within(line, token).on_line(i + 1),
+                    format!(
+                        "failed to parse {} (at index {}) as a number: {}",
+                        token,
+                        numbers.len(),
+                        e
+                    ),
+                )
+            })?;
+            numbers.push(n);
+        }
+    }
+    Ok(numbers)
+}
+
+/// Check that displaying `value` and parsing the result again gives back
+/// an equal value.
+///
+/// # Examples
+///
+/// ```
+/// use utils::parse;
+///
+/// assert_eq!(parse::roundtrip(&42), Ok(()));
+/// assert!(parse::roundtrip(&1.0e100).is_ok());
+/// assert!(parse::roundtrip(&f64::NAN).is_err());
+/// ```
+pub fn roundtrip<T>(value: &T) -> Result<(), String>
+where
+    T: fmt::Display + fmt::Debug + std::str::FromStr + PartialEq,
+    T::Err: fmt::Debug,
+{
+    let displayed = value.to_string();
+    match displayed.parse::<T>() {
+        Ok(parsed) if parsed == *value => Ok(()),
+        Ok(parsed) => Err(format!(
+            "{:?} was displayed as {:?} but parsed back as {:?}",
+            value, displayed, parsed
+        )),
+        Err(e) => Err(format!(
+            "{:?} was displayed as {:?} which failed to parse: {:?}",
+            value, displayed, e
+        )),
+    }
+}
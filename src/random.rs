@@ -0,0 +1,86 @@
+use std::ops::RangeInclusive;
+
+/// A small, seeded pseudo-random number generator (SplitMix64), good
+/// enough for generating test inputs but not for anything secret.
+///
+/// The same seed always produces the same numbers.
+///
+/// # Examples
+///
+/// ```
+/// use utils::random::Rng;
+///
+/// let mut a = Rng::new(42);
+/// let mut b = Rng::new(42);
+/// let numbers: Vec<u64> = (0..3).map(|_| a.next_u64()).collect();
+/// assert_eq!(numbers, (0..3).map(|_| b.next_u64()).collect::<Vec<_>>());
+/// assert_ne!(numbers[0], Rng::new(43).next_u64());
+///
+/// let roll = a.range(1..=6);
+/// assert!((1..=6).contains(&roll));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number from `0` up to but not including `n`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is 0.
+    pub fn below(&mut self, n: usize) -> usize {
+        assert!(n > 0, "cannot pick a number below 0");
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+
+    /// A number in `range`, which must not be empty.
+    pub fn range(&mut self, range: RangeInclusive<i64>) -> i64 {
+        let (start, end) = range.into_inner();
+        assert!(start <= end, "cannot pick a number in an empty range");
+        let width = (end as i128 - start as i128 + 1) as u128;
+        let offset = (self.next_u64() as u128 * width) >> 64;
+        (start as i128 + offset as i128) as i64
+    }
+
+    /// A random item of `items`, or `None` if there are none.
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            None
+        } else {
+            items.get(self.below(items.len()))
+        }
+    }
+
+    /// Put `items` in a random order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::random::Rng;
+    ///
+    /// let mut items: Vec<u32> = (0..10).collect();
+    /// Rng::new(7).shuffle(&mut items);
+    /// assert_ne!(items, (0..10).collect::<Vec<_>>());
+    /// items.sort_unstable();
+    /// assert_eq!(items, (0..10).collect::<Vec<_>>());
+    /// ```
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
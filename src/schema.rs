@@ -0,0 +1,357 @@
+use std::{
+    fmt, fs,
+    ops::{Bound, RangeBounds},
+};
+
+use crate::parse::{ParseError, Span};
+
+/// The number of mismatching lines to report before giving up.
+const MAX_REPORTED: usize = 5;
+
+/// The lowercase ASCII letters, for use in [`Token::OneOf`] and
+/// [`Token::ManyOf`].
+pub const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+
+/// A piece of a line pattern.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    /// Exactly this text.
+    Literal(&'static str),
+    /// One or more ASCII digits.
+    Digits,
+    /// One or more ASCII digits with an optional leading sign.
+    Number,
+    /// One or more ASCII digits forming a number between the two bounds,
+    /// inclusive.
+    Between(u64, u64),
+    /// One or more lowercase ASCII letters.
+    Word,
+    /// A single character from the set.
+    OneOf(&'static str),
+    /// One or more characters from the set.
+    ManyOf(&'static str),
+    /// Whatever is left of the line, possibly nothing.
+    Rest,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Literal(s) => write!(f, "{}", s),
+            Token::Digits => write!(f, "<digits>"),
+            Token::Number => write!(f, "<number>"),
+            Token::Between(min, max) => write!(f, "<number from {} to {}>", min, max),
+            Token::Word => write!(f, "<word>"),
+            Token::OneOf(set) => write!(f, "<one of {}>", set),
+            Token::ManyOf(set) => write!(f, "<any of {}>", set),
+            Token::Rest => write!(f, "<rest>"),
+        }
+    }
+}
+
+/// Check if the whole of `s` matches the pattern.
+///
+/// # Examples
+///
+/// ```
+/// use utils::schema::{self, Token};
+///
+/// let pattern = [Token::Word, Token::Literal(" "), Token::Number];
+/// assert!(schema::matches(&pattern, "jmp -4"));
+/// assert!(schema::matches(&pattern, "acc 12"));
+/// assert!(!schema::matches(&pattern, "jmp"));
+/// assert!(!schema::matches(&pattern, "12 jmp"));
+///
+/// let pattern = [Token::Between(1, 2019)];
+/// assert!(schema::matches(&pattern, "1721"));
+/// assert!(!schema::matches(&pattern, "2020"));
+/// assert!(!schema::matches(&pattern, "0"));
+/// ```
+pub fn matches(pattern: &[Token], s: &str) -> bool {
+    fn longest_prefix(s: &str, f: impl Fn(char) -> bool) -> Vec<usize> {
+        s.char_indices()
+            .take_while(|(_, c)| f(*c))
+            .map(|(i, c)| i + c.len_utf8())
+            .collect()
+    }
+
+    let (token, rest) = match pattern.split_first() {
+        None => return s.is_empty(),
+        Some(split) => split,
+    };
+    let ends = match token {
+        Token::Literal(l) => return s.starts_with(l) && matches(rest, &s[l.len()..]),
+        Token::Rest => return true,
+        Token::OneOf(set) => {
+            return s
+                .chars()
+                .next()
+                .filter(|c| set.contains(*c))
+                .map(|c| matches(rest, &s[c.len_utf8()..]))
+                .unwrap_or(false)
+        }
+        Token::Number => {
+            let unsigned = s.strip_prefix(|c| c == '+' || c == '-').unwrap_or(s);
+            return matches(&[&[Token::Digits], rest].concat(), unsigned);
+        }
+        Token::Digits => longest_prefix(s, |c| c.is_ascii_digit()),
+        Token::Between(min, max) => longest_prefix(s, |c| c.is_ascii_digit())
+            .into_iter()
+            .filter(|end| {
+                s[..*end]
+                    .parse::<u64>()
+                    .map(|n| (*min..=*max).contains(&n))
+                    .unwrap_or(false)
+            })
+            .collect(),
+        Token::Word => longest_prefix(s, |c| c.is_ascii_lowercase()),
+        Token::ManyOf(set) => longest_prefix(s, |c| set.contains(c)),
+    };
+    ends.iter().rev().any(|end| matches(rest, &s[*end..]))
+}
+
+/// The expected shape of a puzzle input.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Schema {
+    /// Every line matches the pattern.
+    Lines(Vec<Token>),
+    /// A rectangular grid where every cell is one of the characters.
+    Grid(&'static str),
+    /// A first line matching the pattern, followed by lines matching the
+    /// schema.
+    Titled(Vec<Token>, Box<Schema>),
+    /// Exactly one section per schema, separated by blank lines.
+    Sections(Vec<Schema>),
+    /// Any number of sections separated by blank lines, all matching the
+    /// schema.
+    Blocks(Box<Schema>),
+    /// Input matching the schema, with at least as many lines as the
+    /// first number and at most as many as the second one, if any. Built
+    /// with [`Schema::counted`].
+    Counted(usize, Option<usize>, Box<Schema>),
+}
+
+impl Schema {
+    /// Input matching `schema`, with a number of lines in the range.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::schema::{Schema, Token};
+    ///
+    /// let lines = || Schema::Lines(vec![Token::Digits]);
+    /// assert_eq!(Schema::counted(2.., lines()), Schema::Counted(2, None, Box::new(lines())));
+    /// assert_eq!(Schema::counted(..3, lines()), Schema::Counted(0, Some(2), Box::new(lines())));
+    ///
+    /// let errors = Schema::counted(2.., lines()).validate("1").unwrap_err();
+    /// assert_eq!(errors[0].message, "expected at least 2 lines, but found 1");
+    /// ```
+    pub fn counted<R: RangeBounds<usize>>(lines: R, schema: Schema) -> Schema {
+        let min = match lines.start_bound() {
+            Bound::Included(n) => *n,
+            Bound::Excluded(n) => n + 1,
+            Bound::Unbounded => 0,
+        };
+        let max = match lines.end_bound() {
+            Bound::Included(n) => Some(*n),
+            Bound::Excluded(n) => Some(n.saturating_sub(1)),
+            Bound::Unbounded => None,
+        };
+        Schema::Counted(min, max, Box::new(schema))
+    }
+
+    /// Check `input` against the schema, returning every mismatching line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::parse::Span;
+    /// use utils::schema::{Schema, Token};
+    ///
+    /// let schema = Schema::Lines(vec![Token::Digits]);
+    /// assert_eq!(schema.validate("1\n2\n3\n"), Ok(()));
+    ///
+    /// let errors = schema.validate("1\nacc +2\n3\nnop +0").unwrap_err();
+    /// assert_eq!(errors.len(), 2);
+    /// assert_eq!(errors[0].span, Span::new(2, 1, 6));
+    /// assert_eq!(errors[0].message, "expected a line like <digits>");
+    ///
+    /// let schema = Schema::counted(1..=2, schema);
+    /// let errors = schema.validate("1\n2\n3").unwrap_err();
+    /// assert_eq!(errors[0].span, Span::new(3, 1, 1));
+    /// assert_eq!(errors[0].message, "expected between 1 and 2 lines, but found 3");
+    /// ```
+    pub fn validate(&self, input: &str) -> Result<(), Vec<ParseError>> {
+        let lines: Vec<&str> = input.lines().collect();
+        if lines.iter().all(|l| l.trim().is_empty()) {
+            return Err(vec![ParseError::new(Span::new(1, 1, 1), "input is empty")]);
+        }
+        let mut errors = vec![];
+        self.check(&lines, 1, &mut errors);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn check(&self, lines: &[&str], first_line: usize, errors: &mut Vec<ParseError>) {
+        let numbered = lines.iter().enumerate().map(|(i, l)| (first_line + i, *l));
+        match self {
+            Schema::Lines(pattern) => {
+                for (n, l) in numbered {
+                    check_line(pattern, n, l, errors);
+                }
+            }
+            Schema::Grid(charset) => {
+                let width = lines.first().map_or(0, |l| l.chars().count());
+                for (n, l) in numbered {
+                    if let Some((i, c)) = l.chars().enumerate().find(|(_, c)| !charset.contains(*c))
+                    {
+                        errors.push(ParseError::new(
+                            Span::new(n, i + 1, 1),
+                            format!("expected one of {}, but found {}", charset, c),
+                        ));
+                    } else if l.chars().count() != width {
+                        errors.push(ParseError::new(
+                            Span::new(n, 1, l.chars().count().max(1)),
+                            format!("expected a grid row of width {}", width),
+                        ));
+                    }
+                }
+            }
+            Schema::Titled(pattern, body) => match lines.split_first() {
+                None => errors.push(ParseError::new(
+                    Span::new(first_line, 1, 1),
+                    "expected a title line",
+                )),
+                Some((title, rest)) => {
+                    check_line(pattern, first_line, title, errors);
+                    body.check(rest, first_line + 1, errors);
+                }
+            },
+            Schema::Sections(schemas) => {
+                let sections = sections(lines, first_line);
+                if sections.len() != schemas.len() {
+                    let last = first_line + lines.len().max(1) - 1;
+                    errors.push(ParseError::new(
+                        Span::new(last, 1, 1),
+                        format!(
+                            "expected {} sections separated by blank lines, but found {}",
+                            schemas.len(),
+                            sections.len()
+                        ),
+                    ));
+                }
+                for (schema, (start, section)) in schemas.iter().zip(sections) {
+                    schema.check(section, start, errors);
+                }
+            }
+            Schema::Blocks(schema) => {
+                for (start, section) in sections(lines, first_line) {
+                    schema.check(section, start, errors);
+                }
+            }
+            Schema::Counted(min, max, schema) => {
+                let count = lines.len();
+                if count < *min || max.map(|max| count > max).unwrap_or(false) {
+                    let expected = match max {
+                        None => format!("at least {}", min),
+                        Some(max) if max == min => min.to_string(),
+                        Some(max) => format!("between {} and {}", min, max),
+                    };
+                    errors.push(ParseError::new(
+                        Span::new(first_line + lines.len().max(1) - 1, 1, 1),
+                        format!("expected {} lines, but found {}", expected, lines.len()),
+                    ));
+                }
+                schema.check(lines, first_line, errors);
+            }
+        }
+    }
+
+    /// Check the input in `input_file` against the schema for `day`,
+    /// printing the first mismatching lines.
+    pub fn validate_file(&self, day: &str, input_file: &str) -> Result<(), String> {
+        let content = fs::read_to_string(input_file)
+            .map_err(|err| format!("failed to read input: {}", err))?;
+        match self.validate(&content) {
+            Ok(()) => {
+                println!("{} looks like input for {}", input_file, day);
+                Ok(())
+            }
+            Err(errors) => {
+                for e in errors.iter().take(MAX_REPORTED) {
+                    eprintln!("{}\n", e.render(&content));
+                }
+                if errors.len() > MAX_REPORTED {
+                    eprintln!("... and {} more\n", errors.len() - MAX_REPORTED);
+                }
+                Err(format!(
+                    "{} does not look like input for {} ({} mismatching lines)",
+                    input_file,
+                    day,
+                    errors.len()
+                ))
+            }
+        }
+    }
+}
+
+fn check_line(pattern: &[Token], n: usize, line: &str, errors: &mut Vec<ParseError>) {
+    if !matches(pattern, line) {
+        let expected: String = pattern.iter().map(|t| t.to_string()).collect();
+        errors.push(ParseError::new(
+            Span::new(n, 1, line.chars().count().max(1)),
+            format!("expected a line like {}", expected),
+        ));
+    }
+}
+
+/// Split lines into non-empty groups separated by blank lines, together
+/// with the line number each group starts at.
+fn sections<'a, 'b>(lines: &'b [&'a str], first_line: usize) -> Vec<(usize, &'b [&'a str])> {
+    let mut sections = vec![];
+    let mut start = 0;
+    for i in 0..=lines.len() {
+        if i == lines.len() || lines[i].trim().is_empty() {
+            if i > start {
+                sections.push((first_line + start, &lines[start..i]));
+            }
+            start = i + 1;
+        }
+    }
+    sections
+}
+
+/// Handle `<day> --validate <input>`, checking the input against the
+/// schema of the day instead of solving it.
+///
+/// Returns `None` when the program was run with other arguments.
+///
+/// # Examples
+///
+/// ```no_run
+/// use utils::schema::{self, Schema, Token};
+///
+/// fn main() -> Result<(), String> {
+///     if let Some(result) = schema::validate_args("day1", schema) {
+///         return result;
+///     }
+///     // Solve the puzzle.
+///     Ok(())
+/// }
+///
+/// fn schema() -> Schema {
+///     Schema::Lines(vec![Token::Digits])
+/// }
+/// ```
+pub fn validate_args(day: &str, schema: fn() -> Schema) -> Option<Result<(), String>> {
+    let args: Vec<String> = std::env::args().collect();
+    match args.as_slice() {
+        [_, flag, input_file] if flag == "--validate" => {
+            Some(schema().validate_file(day, input_file))
+        }
+        _ => None,
+    }
+}
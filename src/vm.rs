@@ -0,0 +1,519 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    ops::{Index, RangeInclusive},
+    str::FromStr,
+};
+
+use crate::parse::{self, ParseError};
+
+pub mod analysis;
+pub mod asm;
+pub mod debug;
+pub mod dot;
+pub mod generate;
+pub mod isa;
+pub mod mutate;
+pub mod trace;
+
+use isa::{InstructionSet, State};
+use trace::Profile;
+
+/// The values the accumulator of the handheld game console can hold.
+const ACC_RANGE: RangeInclusive<i64> = i32::MIN as i64..=i32::MAX as i64;
+
+/// An instruction of the handheld game console.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    /// Add the operand to the accumulator.
+    Acc(i32),
+    /// Jump to the instruction the operand is away from this one.
+    Jmp(i32),
+    /// Do nothing.
+    Nop(i32),
+}
+
+impl Instruction {
+    /// Create the instruction called `opcode`, if there is one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::vm::Instruction;
+    ///
+    /// assert_eq!(Instruction::new("jmp", -2), Some(Instruction::Jmp(-2)));
+    /// assert_eq!(Instruction::new("mul", 2), None);
+    /// ```
+    pub fn new(opcode: &str, operand: i32) -> Option<Instruction> {
+        match opcode {
+            "acc" => Some(Instruction::Acc(operand)),
+            "jmp" => Some(Instruction::Jmp(operand)),
+            "nop" => Some(Instruction::Nop(operand)),
+            _ => None,
+        }
+    }
+
+    /// The name of the instruction, like `acc`.
+    pub fn opcode(&self) -> &'static str {
+        match self {
+            Instruction::Acc(_) => "acc",
+            Instruction::Jmp(_) => "jmp",
+            Instruction::Nop(_) => "nop",
+        }
+    }
+
+    pub fn operand(&self) -> i32 {
+        match self {
+            Instruction::Acc(n) | Instruction::Jmp(n) | Instruction::Nop(n) => *n,
+        }
+    }
+
+    /// Run the instruction on the registers of a machine.
+    pub fn execute(self, state: &mut State) -> Result<(), TrapKind> {
+        match self {
+            Instruction::Acc(n) => {
+                let acc = state
+                    .acc()
+                    .checked_add(n.into())
+                    .filter(|acc| ACC_RANGE.contains(acc))
+                    .ok_or(TrapKind::Overflow)?;
+                state.set_acc(acc);
+            }
+            Instruction::Jmp(n) => state.jump(n.into())?,
+            Instruction::Nop(_) => {}
+        }
+        Ok(())
+    }
+}
+
+/// Parse an instruction like `jmp -4`, with the instructions of the
+/// default [`InstructionSet`].
+///
+/// # Examples
+///
+/// ```
+/// use utils::parse::Span;
+/// use utils::vm::Instruction;
+///
+/// assert_eq!("acc +3".parse(), Ok(Instruction::Acc(3)));
+/// assert_eq!("jmp -4".parse(), Ok(Instruction::Jmp(-4)));
+///
+/// let err = "mul +2".parse::<Instruction>().unwrap_err();
+/// assert_eq!(err.span, Span::new(1, 1, 3));
+/// assert_eq!(err.message, "unknown opcode mul, expected acc, jmp or nop");
+/// assert_eq!("nop 2".parse::<Instruction>().unwrap_err().span, Span::new(1, 5, 1));
+/// ```
+impl FromStr for Instruction {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        InstructionSet::default().parse_instruction(s)
+    }
+}
+
+/// Parse an operand, which always starts with its sign.
+fn parse_operand<T>(s: &str) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    if !s.starts_with(&['+', '-'][..]) {
+        return Err(format!("expected a signed number like +{}", s));
+    }
+    s.parse()
+        .map_err(|e| format!("failed to parse operand {}: {}", s, e))
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {:+}", self.opcode(), self.operand())
+    }
+}
+
+/// A list of instructions, one per line.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Program {
+    instructions: Vec<Instruction>,
+}
+
+impl Program {
+    pub fn new(instructions: Vec<Instruction>) -> Program {
+        Program { instructions }
+    }
+
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+
+    pub fn get(&self, pc: usize) -> Option<&Instruction> {
+        self.instructions.get(pc)
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Instruction> {
+        self.instructions.iter()
+    }
+}
+
+/// Parse a program with one instruction per line.
+///
+/// # Examples
+///
+/// ```
+/// use utils::parse::Span;
+/// use utils::vm::{Instruction, Program};
+///
+/// let p: Program = "nop +0\nacc +1\njmp -2".parse().unwrap();
+/// assert_eq!(p.len(), 3);
+/// assert_eq!(p[2], Instruction::Jmp(-2));
+/// assert_eq!(p.to_string(), "nop +0\nacc +1\njmp -2\n");
+///
+/// let err = "nop +0\nacc x".parse::<Program>().unwrap_err();
+/// assert_eq!(err.span, Span::new(2, 5, 1));
+/// ```
+impl FromStr for Program {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let set = InstructionSet::default();
+        parse::lines(s, |line| set.parse_instruction(line)).map(Program::new)
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for instruction in self.iter() {
+            writeln!(f, "{}", instruction)?;
+        }
+        Ok(())
+    }
+}
+
+impl Executable for Program {
+    fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    fn execute(&self, pc: usize, state: &mut State) -> Result<(), TrapKind> {
+        self[pc].execute(state)
+    }
+
+    fn render(&self, pc: usize) -> String {
+        self[pc].to_string()
+    }
+
+    fn branches_on_registers(&self) -> bool {
+        false
+    }
+}
+
+impl Index<usize> for Program {
+    type Output = Instruction;
+
+    fn index(&self, pc: usize) -> &Instruction {
+        &self.instructions[pc]
+    }
+}
+
+/// A program a [`Machine`] can run: a [`Program`] of the handheld game
+/// console, or [`isa::Code`] parsed with any instruction set.
+pub trait Executable {
+    /// The number of instructions.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Run the instruction at `pc`.
+    fn execute(&self, pc: usize, state: &mut State) -> Result<(), TrapKind>;
+
+    /// The instruction at `pc` written as in the source of the program.
+    fn render(&self, pc: usize) -> String;
+
+    /// Whether some instruction can decide where to go next from the
+    /// registers, see [`isa::Opcode::branches_on_registers`].
+    fn branches_on_registers(&self) -> bool;
+}
+
+/// Why a machine stopped running a program before it ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrapKind {
+    /// A `jmp` pointed outside of the program, to `target`.
+    JumpOutOfBounds { target: i64 },
+    /// The accumulator overflowed.
+    Overflow,
+}
+
+/// A program stopping at the instruction at `pc` because of an error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Trap {
+    pub kind: TrapKind,
+    pub pc: usize,
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            TrapKind::JumpOutOfBounds { target } => write!(
+                f,
+                "the jmp at {} jumps outside of the program, to {}",
+                self.pc, target
+            ),
+            TrapKind::Overflow => write!(f, "the accumulator overflowed at {}", self.pc),
+        }
+    }
+}
+
+/// How running a program ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RunOutcome {
+    /// The program ran past its last instruction.
+    Terminated { acc: i64 },
+    /// The instruction at `pc` led back to `loop_start`, which had
+    /// already been run, so the program would run forever. `acc` is the
+    /// accumulator right before running `loop_start` again.
+    ///
+    /// For programs with instructions that decide where to go from the
+    /// registers, `loop_start` had already been run with the same
+    /// registers.
+    InfiniteLoop {
+        acc: i64,
+        pc: usize,
+        loop_start: usize,
+    },
+    /// The program stopped because of an error.
+    Trap { kind: TrapKind, pc: usize },
+    /// The program neither ended nor looped within the allowed number of
+    /// steps.
+    StepLimitExceeded,
+}
+
+impl fmt::Display for RunOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RunOutcome::Terminated { acc } => write!(f, "terminated with acc {}", acc),
+            RunOutcome::InfiniteLoop {
+                acc,
+                pc,
+                loop_start,
+            } => write!(
+                f,
+                "looped from {} back to {} with acc {}",
+                pc, loop_start, acc
+            ),
+            RunOutcome::Trap { kind, pc } => write!(f, "stopped because {}", Trap { kind, pc }),
+            RunOutcome::StepLimitExceeded => write!(f, "ran out of steps"),
+        }
+    }
+}
+
+/// The state of the handheld game console while running a program,
+/// either a [`Program`] or code using instructions of its own, see
+/// [`isa::InstructionSet`].
+///
+/// # Examples
+///
+/// ```
+/// use utils::vm::{Machine, Program};
+///
+/// let p: Program = "acc +2\njmp +2\nacc +5\nacc -1".parse().unwrap();
+/// let mut m = Machine::new();
+/// while !m.has_halted() {
+///     m.step(&p).unwrap();
+/// }
+/// assert_eq!(m.acc(), 1);
+/// assert_eq!(m.pc(), 4);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Machine {
+    state: State,
+    halted: bool,
+    profile: Option<Profile>,
+}
+
+impl Default for Machine {
+    fn default() -> Machine {
+        Machine::new()
+    }
+}
+
+impl Machine {
+    pub fn new() -> Machine {
+        Machine {
+            state: State::new(&["acc"]),
+            halted: false,
+            profile: None,
+        }
+    }
+
+    /// A machine with every register of `set` at 0.
+    pub fn for_set(set: &InstructionSet) -> Machine {
+        Machine {
+            state: State::new(set.registers()),
+            ..Machine::new()
+        }
+    }
+
+    /// The same machine, but recording every instruction it runs from
+    /// now on.
+    pub fn traced(self) -> Machine {
+        Machine {
+            profile: Some(Profile::default()),
+            ..self
+        }
+    }
+
+    /// What the machine has done so far, if it is traced.
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// The index of the next instruction to run.
+    pub fn pc(&self) -> usize {
+        self.state.pc
+    }
+
+    /// The value of the accumulator.
+    pub fn acc(&self) -> i64 {
+        self.state.acc
+    }
+
+    /// The registers of the machine.
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    /// Check if the program has ended by running past its last
+    /// instruction.
+    pub fn has_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Run the next instruction of the program.
+    ///
+    /// Does nothing once the program has halted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::vm::{Machine, Program, Trap, TrapKind};
+    ///
+    /// let p: Program = "nop +0\njmp -2".parse().unwrap();
+    /// let mut m = Machine::new();
+    /// assert_eq!(m.step(&p), Ok(()));
+    /// let trap = Trap { kind: TrapKind::JumpOutOfBounds { target: -1 }, pc: 1 };
+    /// assert_eq!(m.step(&p), Err(trap));
+    /// assert_eq!(m.pc(), 1);
+    /// ```
+    pub fn step<P: Executable + ?Sized>(&mut self, program: &P) -> Result<(), Trap> {
+        if self.halted {
+            return Ok(());
+        }
+        let pc = self.state.pc;
+        if pc == program.len() {
+            self.halted = true;
+            return Ok(());
+        }
+        let acc_before = self.acc();
+        let trap = |kind| Trap { kind, pc };
+        self.state.next = pc as i64 + 1;
+        program.execute(pc, &mut self.state).map_err(trap)?;
+        let target = self.state.next;
+        if target < 0 || target as usize > program.len() {
+            return Err(trap(TrapKind::JumpOutOfBounds { target }));
+        }
+        self.state.pc = target as usize;
+        if let Some(profile) = &mut self.profile {
+            profile.record(trace::Step {
+                pc,
+                instruction: program.render(pc),
+                acc_before,
+                acc_after: self.state.acc,
+            });
+        }
+        Ok(())
+    }
+
+    /// Run the program until it ends, loops or traps.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::vm::{Machine, Program, RunOutcome};
+    ///
+    /// let p: Program = "acc +1\njmp +2\nacc +5\nacc +1".parse().unwrap();
+    /// assert_eq!(Machine::new().run(&p), RunOutcome::Terminated { acc: 2 });
+    ///
+    /// let p: Program = "nop +0\nacc +1\njmp -1".parse().unwrap();
+    /// assert_eq!(
+    ///     Machine::new().run(&p),
+    ///     RunOutcome::InfiniteLoop { acc: 1, pc: 2, loop_start: 1 }
+    /// );
+    /// ```
+    pub fn run<P: Executable + ?Sized>(&mut self, program: &P) -> RunOutcome {
+        self.run_limited(program, usize::MAX)
+    }
+
+    /// Like [`Machine::run`], but giving up after running `max_steps`
+    /// instructions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::vm::{Machine, Program, RunOutcome};
+    ///
+    /// let p: Program = "nop +0\nnop +0\nnop +0".parse().unwrap();
+    /// assert_eq!(Machine::new().run_limited(&p, 2), RunOutcome::StepLimitExceeded);
+    /// assert_eq!(Machine::new().run_limited(&p, 3), RunOutcome::Terminated { acc: 0 });
+    /// ```
+    pub fn run_limited<P>(&mut self, program: &P, max_steps: usize) -> RunOutcome
+    where
+        P: Executable + ?Sized,
+    {
+        // Without instructions deciding where to go from the registers,
+        // coming back to an instruction at all means going round forever.
+        let by_pc = !program.branches_on_registers();
+        let mut visited = vec![false; if by_pc { program.len() } else { 0 }];
+        let mut seen = HashSet::new();
+        let mut steps = 0;
+        let mut last = self.state.pc;
+        loop {
+            let pc = self.state.pc;
+            if pc == program.len() {
+                self.halted = true;
+                return RunOutcome::Terminated { acc: self.acc() };
+            }
+            let repeated = if by_pc {
+                std::mem::replace(&mut visited[pc], true)
+            } else {
+                !seen.insert((pc, self.state.acc, self.state.registers.clone()))
+            };
+            if repeated {
+                if let Some(profile) = &mut self.profile {
+                    profile.revisit(pc);
+                }
+                return RunOutcome::InfiniteLoop {
+                    acc: self.acc(),
+                    pc: last,
+                    loop_start: pc,
+                };
+            }
+            if steps == max_steps {
+                return RunOutcome::StepLimitExceeded;
+            }
+            last = pc;
+            if let Err(Trap { kind, pc }) = self.step(program) {
+                return RunOutcome::Trap { kind, pc };
+            }
+            steps += 1;
+        }
+    }
+}
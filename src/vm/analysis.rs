@@ -0,0 +1,242 @@
+use std::ops::Range;
+
+use super::{Instruction, Machine, Program, RunOutcome};
+
+/// Where the instruction at `pc` continues to, or `None` if it jumps
+/// outside of the program. Running past the last instruction continues
+/// to `program.len()`.
+pub fn successor(program: &Program, pc: usize, instruction: Instruction) -> Option<usize> {
+    let target = match instruction {
+        Instruction::Acc(_) | Instruction::Nop(_) => pc as i64 + 1,
+        Instruction::Jmp(n) => pc as i64 + n as i64,
+    };
+    if target < 0 || target as usize > program.len() {
+        None
+    } else {
+        Some(target as usize)
+    }
+}
+
+/// Split the program into basic blocks: runs of instructions that are
+/// only entered at their first instruction and only jump at their last.
+///
+/// # Examples
+///
+/// ```
+/// use utils::vm::{analysis, Program};
+///
+/// let p: Program = "nop +0\nacc +1\njmp -1\njmp +2\nacc +3".parse().unwrap();
+/// assert_eq!(analysis::basic_blocks(&p), vec![0..1, 1..3, 3..4, 4..5]);
+/// ```
+pub fn basic_blocks(program: &Program) -> Vec<Range<usize>> {
+    let mut leaders = vec![false; program.len() + 1];
+    if let Some(first) = leaders.first_mut() {
+        *first = true;
+    }
+    for (pc, instruction) in program.iter().enumerate() {
+        if let Instruction::Jmp(_) = instruction {
+            leaders[pc + 1] = true;
+            if let Some(target) = successor(program, pc, *instruction) {
+                leaders[target] = true;
+            }
+        }
+    }
+    let starts: Vec<usize> = (0..program.len()).filter(|pc| leaders[*pc]).collect();
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, start)| *start..starts.get(i + 1).copied().unwrap_or(program.len()))
+        .collect()
+}
+
+/// The control flow graph of a program, where every instruction leads to
+/// exactly one other instruction or to the end of the program.
+///
+/// The end of the program is the node `program.len()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cfg {
+    successors: Vec<Option<usize>>,
+    predecessors: Vec<Vec<usize>>,
+}
+
+impl Cfg {
+    pub fn new(program: &Program) -> Cfg {
+        let successors: Vec<Option<usize>> = program
+            .iter()
+            .enumerate()
+            .map(|(pc, i)| successor(program, pc, *i))
+            .collect();
+        let mut predecessors = vec![vec![]; program.len() + 1];
+        for (pc, next) in successors.iter().enumerate() {
+            if let Some(next) = next {
+                predecessors[*next].push(pc);
+            }
+        }
+        Cfg {
+            successors,
+            predecessors,
+        }
+    }
+
+    /// The node for the end of the program.
+    pub fn end(&self) -> usize {
+        self.successors.len()
+    }
+
+    /// Where the instruction at `pc` leads, or `None` for the end of the
+    /// program and for jumps outside of it.
+    pub fn successor(&self, pc: usize) -> Option<usize> {
+        self.successors.get(pc).copied().flatten()
+    }
+
+    /// The instructions leading to `pc`.
+    pub fn predecessors(&self, pc: usize) -> &[usize] {
+        &self.predecessors[pc]
+    }
+
+    /// Find every node from which running the program reaches its end,
+    /// by walking the edges backwards from the end.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::vm::{analysis::Cfg, Program};
+    ///
+    /// let p: Program = "nop +0\njmp +2\njmp -1\nacc +1".parse().unwrap();
+    /// let cfg = Cfg::new(&p);
+    /// assert_eq!(cfg.reaching_end(), vec![true, true, true, true, true]);
+    ///
+    /// let p: Program = "jmp +2\njmp -1\njmp -1\nacc +1".parse().unwrap();
+    /// assert_eq!(Cfg::new(&p).reaching_end(), vec![false, false, false, true, true]);
+    /// ```
+    pub fn reaching_end(&self) -> Vec<bool> {
+        let mut reaching = vec![false; self.end() + 1];
+        let mut stack = vec![self.end()];
+        reaching[self.end()] = true;
+        while let Some(pc) = stack.pop() {
+            for previous in self.predecessors(pc) {
+                if !reaching[*previous] {
+                    reaching[*previous] = true;
+                    stack.push(*previous);
+                }
+            }
+        }
+        reaching
+    }
+
+    /// The instructions run when starting from the first one, in order,
+    /// until the program ends, jumps outside of itself or comes back to
+    /// an instruction it has already run.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::vm::{analysis::Cfg, Program};
+    ///
+    /// let p: Program = "nop +0\nacc +1\njmp +2\nacc +3\njmp -2".parse().unwrap();
+    /// assert_eq!(Cfg::new(&p).executed_path(), vec![0, 1, 2, 4]);
+    /// ```
+    pub fn executed_path(&self) -> Vec<usize> {
+        let mut visited = vec![false; self.end()];
+        let mut path = vec![];
+        let mut pc = Some(0);
+        while let Some(p) = pc {
+            if p == self.end() || visited[p] {
+                break;
+            }
+            visited[p] = true;
+            path.push(p);
+            pc = self.successor(p);
+        }
+        path
+    }
+}
+
+/// A single instruction replaced to make a program end.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Repair {
+    /// The index of the replaced instruction.
+    pub pc: usize,
+    /// The instruction put in its place.
+    pub instruction: Instruction,
+    /// The accumulator when the repaired program ends.
+    pub acc: i64,
+}
+
+impl Repair {
+    /// The program with the repair applied.
+    pub fn apply(&self, program: &Program) -> Program {
+        let mut instructions = program.instructions().to_vec();
+        instructions[self.pc] = self.instruction;
+        Program::new(instructions)
+    }
+}
+
+/// Find the `jmp` or `nop` to flip for the program to end, in time
+/// linear in the length of the program.
+///
+/// Flipping an instruction only matters if the program runs it, and only
+/// helps if the flipped instruction leads somewhere that reaches the end.
+/// The first instruction on the executed path satisfying both is the
+/// repair: the path up to it has no loops, and the instructions after
+/// the flip cannot lead back to it, as it would have reached the end
+/// already.
+///
+/// # Examples
+///
+/// ```
+/// use utils::vm::{analysis, Instruction, Program};
+///
+/// let p: Program = "nop +0\nacc +1\njmp +4\nacc +3\njmp -3\nacc -99\nacc +1\njmp -4\nacc +6"
+///     .parse()
+///     .unwrap();
+/// let repair = analysis::repair(&p).unwrap();
+/// assert_eq!((repair.pc, repair.instruction, repair.acc), (7, Instruction::Nop(-4), 8));
+/// assert_eq!(analysis::repair_brute_force(&p), Some(repair));
+/// ```
+pub fn repair(program: &Program) -> Option<Repair> {
+    let cfg = Cfg::new(program);
+    let reaching = cfg.reaching_end();
+    cfg.executed_path().into_iter().find_map(|pc| {
+        let instruction = flip(program[pc])?;
+        let next = successor(program, pc, instruction)?;
+        if !reaching[next] {
+            return None;
+        }
+        // The repaired program can still overflow the accumulator.
+        terminating(program, pc, instruction)
+    })
+}
+
+/// Find the `jmp` or `nop` to flip for the program to end by trying
+/// every one of them in order and running the result.
+///
+/// This takes quadratic time, but is simple enough to check [`repair`]
+/// against. The two agree whenever there is only one possible repair;
+/// otherwise this finds the one closest to the start of the program.
+pub fn repair_brute_force(program: &Program) -> Option<Repair> {
+    (0..program.len()).find_map(|pc| terminating(program, pc, flip(program[pc])?))
+}
+
+/// Replace the instruction at `pc` and run the program, returning the
+/// repair if the program ends.
+fn terminating(program: &Program, pc: usize, instruction: Instruction) -> Option<Repair> {
+    let repair = Repair {
+        pc,
+        instruction,
+        acc: 0,
+    };
+    match Machine::new().run(&repair.apply(program)) {
+        RunOutcome::Terminated { acc } => Some(Repair { acc, ..repair }),
+        _ => None,
+    }
+}
+
+/// Swap a `jmp` for a `nop` and the other way around.
+fn flip(instruction: Instruction) -> Option<Instruction> {
+    match instruction {
+        Instruction::Jmp(n) => Some(Instruction::Nop(n)),
+        Instruction::Nop(n) => Some(Instruction::Jmp(n)),
+        Instruction::Acc(_) => None,
+    }
+}
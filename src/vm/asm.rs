@@ -0,0 +1,241 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{analysis, Instruction, Program};
+use crate::parse::{ParseError, Span};
+
+/// Assemble a program written with labels, comments and constants into
+/// the relative jumps the machine runs.
+///
+/// Every line holds at most one of:
+///
+/// - an instruction like `acc +1`, `jmp loop` or `acc STEP`,
+/// - a label like `loop:`, optionally followed by an instruction,
+/// - a constant like `.const STEP -3`.
+///
+/// Everything after a `;` is a comment. Operands are numbers, with or
+/// without a sign, names of constants, or for `jmp` and `nop` names of
+/// labels, which become the offset from the instruction to the label. A
+/// label after the last instruction points at the end of the program.
+///
+/// # Examples
+///
+/// ```
+/// use utils::vm::{asm, Program};
+///
+/// let source = "
+///     .const STEP 2     ; added every time around
+///     nop +0
+/// loop:
+///     acc STEP
+///     jmp loop
+/// ";
+/// let expected: Program = "nop +0\nacc +2\njmp -1".parse().unwrap();
+/// assert_eq!(asm::assemble(source), Ok(expected));
+/// ```
+///
+/// Errors point at the offending part of the source:
+///
+/// ```
+/// use utils::parse::Span;
+/// use utils::vm::asm;
+///
+/// let err = asm::assemble("start:\n  jmp finish").unwrap_err();
+/// assert_eq!(err.span, Span::new(2, 7, 6));
+/// assert_eq!(err.message, "finish is not a number, constant or label");
+///
+/// let err = asm::assemble("a:\nacc +1\na: nop +0").unwrap_err();
+/// assert_eq!(err.message, "label a is defined twice");
+/// ```
+pub fn assemble(source: &str) -> Result<Program, ParseError> {
+    let mut labels: HashMap<&str, usize> = HashMap::new();
+    let mut constants: HashMap<&str, i32> = HashMap::new();
+    let mut statements = vec![];
+    for (i, line) in source.lines().enumerate() {
+        let n = i + 1;
+        let at = |part: &str| Span::within(line, part).on_line(n);
+        let code = line.split(';').next().unwrap_or("");
+        let mut words: Vec<&str> = code.split_whitespace().collect();
+        if let Some(label) = words.first().and_then(|w| w.strip_suffix(':')) {
+            check_name(label, at(words[0]))?;
+            if labels.insert(label, statements.len()).is_some() {
+                return Err(ParseError::new(
+                    at(words[0]),
+                    format!("label {} is defined twice", label),
+                ));
+            }
+            words.remove(0);
+        }
+        match words.as_slice() {
+            [] => {}
+            [".const", name, value] => {
+                check_name(name, at(name))?;
+                let value = parse_number(value).map_err(|e| ParseError::new(at(value), e))?;
+                if constants.insert(name, value).is_some() {
+                    return Err(ParseError::new(
+                        at(name),
+                        format!("constant {} is defined twice", name),
+                    ));
+                }
+            }
+            [".const", ..] => {
+                return Err(ParseError::new(
+                    at(words[0]),
+                    "expected a constant like .const NAME VALUE",
+                ))
+            }
+            [opcode, operand] => statements.push((n, *opcode, *operand, line)),
+            [opcode] => {
+                return Err(ParseError::new(
+                    Span::end_of(line).on_line(n),
+                    format!("missing operand for {}", opcode),
+                ))
+            }
+            [_, _, extra, ..] => {
+                return Err(ParseError::new(
+                    at(extra),
+                    "expected nothing after the operand",
+                ))
+            }
+        }
+    }
+
+    let mut instructions = Vec::with_capacity(statements.len());
+    for (pc, (n, opcode, operand, line)) in statements.into_iter().enumerate() {
+        let at = |part: &str| Span::within(line, part).on_line(n);
+        let instruction: fn(i32) -> Instruction = match opcode {
+            "acc" => Instruction::Acc,
+            "jmp" => Instruction::Jmp,
+            "nop" => Instruction::Nop,
+            _ => {
+                return Err(ParseError::new(
+                    at(opcode),
+                    format!("unknown opcode {}, expected acc, jmp or nop", opcode),
+                ))
+            }
+        };
+        let value = if let Ok(n) = parse_number(operand) {
+            n
+        } else if let Some(value) = constants.get(operand) {
+            *value
+        } else if let Some(target) = labels.get(operand) {
+            if opcode == "acc" {
+                return Err(ParseError::new(
+                    at(operand),
+                    "labels can only be used with jmp and nop",
+                ));
+            }
+            *target as i32 - pc as i32
+        } else {
+            return Err(ParseError::new(
+                at(operand),
+                format!("{} is not a number, constant or label", operand),
+            ));
+        };
+        instructions.push(instruction(value));
+    }
+    Ok(Program::new(instructions))
+}
+
+fn parse_number(s: &str) -> Result<i32, String> {
+    if !s.starts_with(|c: char| c == '+' || c == '-' || c.is_ascii_digit()) {
+        return Err(format!("expected a number, but found {}", s));
+    }
+    s.parse()
+        .map_err(|e| format!("failed to parse {} as a number: {}", s, e))
+}
+
+/// Check that a label or constant is a name that cannot be mistaken for
+/// a number.
+fn check_name(name: &str, span: Span) -> Result<(), ParseError> {
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(ParseError::new(
+            span,
+            format!("{:?} is not a valid name", name),
+        ))
+    }
+}
+
+/// Write a program with labels for the targets of its jumps and a blank
+/// line between its basic blocks.
+///
+/// Labels are named `L0`, `L1` and so on in the order they appear, except
+/// for the end of the program, which is called `end`. Jumps outside of
+/// the program keep their offsets.
+///
+/// # Examples
+///
+/// ```
+/// use utils::vm::{asm, Program};
+///
+/// let p: Program = "nop +0\nacc +1\njmp -1\njmp +2\nacc +3".parse().unwrap();
+/// let source = asm::disassemble(&p);
+/// assert_eq!(
+///     source,
+///     "    nop +0
+///
+/// L0:
+///     acc +1
+///     jmp L0
+///
+///     jmp end
+///
+///     acc +3
+/// end:
+/// "
+/// );
+/// assert_eq!(asm::assemble(&source), Ok(p));
+/// ```
+pub fn disassemble(program: &Program) -> String {
+    let len = program.len();
+    let target = |pc: usize, offset: i32| {
+        let target = pc as i64 + offset as i64;
+        if target >= 0 && target as usize <= len {
+            Some(target as usize)
+        } else {
+            None
+        }
+    };
+    let targets: HashSet<usize> = program
+        .iter()
+        .enumerate()
+        .filter_map(|(pc, i)| match i {
+            Instruction::Jmp(n) => target(pc, *n),
+            _ => None,
+        })
+        .collect();
+    let mut names: HashMap<usize, String> = HashMap::new();
+    for pc in 0..len {
+        if targets.contains(&pc) {
+            let name = format!("L{}", names.len());
+            names.insert(pc, name);
+        }
+    }
+    names.insert(len, "end".to_string());
+
+    let blocks: HashSet<usize> = analysis::basic_blocks(program)
+        .iter()
+        .map(|b| b.start)
+        .collect();
+    let mut out = String::new();
+    for (pc, instruction) in program.iter().enumerate() {
+        if pc > 0 && blocks.contains(&pc) {
+            out.push('\n');
+        }
+        if targets.contains(&pc) {
+            out.push_str(&format!("{}:\n", names[&pc]));
+        }
+        let line = match (instruction, target(pc, instruction.operand())) {
+            (Instruction::Jmp(_), Some(t)) => format!("jmp {}", names[&t]),
+            _ => instruction.to_string(),
+        };
+        out.push_str(&format!("    {}\n", line));
+    }
+    if targets.contains(&len) {
+        out.push_str("end:\n");
+    }
+    out
+}
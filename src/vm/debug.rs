@@ -0,0 +1,322 @@
+use std::{
+    fmt,
+    io::{self, BufRead, Write},
+};
+
+use super::{Machine, Program};
+
+/// The number of instructions shown before and after the current one
+/// when printing the program.
+const CONTEXT: usize = 2;
+
+const HELP: &str = "commands:
+  step [N], s [N]     run the next N instructions (1 by default)
+  continue, c         run until a breakpoint, watchpoint, loop or the end
+  break PC|OPCODE     stop before running the instruction at PC or any OPCODE
+  delete PC|OPCODE    remove a breakpoint
+  watch acc           stop when the accumulator changes
+  unwatch acc         stop watching the accumulator
+  print, p            show pc, acc and the surrounding instructions
+  back [N]            undo the last N steps (1 by default)
+  help, h             show this help
+  quit, q             leave the debugger";
+
+/// Where the debugger stops while continuing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Before running the instruction at this index.
+    Pc(usize),
+    /// Before running any instruction with this opcode.
+    Opcode(String),
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Breakpoint::Pc(pc) => write!(f, "pc {}", pc),
+            Breakpoint::Opcode(opcode) => write!(f, "opcode {}", opcode),
+        }
+    }
+}
+
+/// An interactive debugger for programs of the handheld game console,
+/// reading commands line by line.
+///
+/// # Examples
+///
+/// ```
+/// use utils::vm::{debug::Debugger, Program};
+///
+/// let program: Program = "nop +0\nacc +1\njmp -1".parse().unwrap();
+/// let mut out = vec![];
+/// let commands = "break jmp\ncontinue\nstep\nprint\nback\nquit\n";
+/// Debugger::new(&program).run(commands.as_bytes(), &mut out).unwrap();
+/// assert_eq!(
+///     String::from_utf8(out).unwrap(),
+///     "breakpoint set at opcode jmp
+/// stopped at breakpoint opcode jmp
+/// pc 2: jmp -1, acc 1
+/// pc 1: acc +1, acc 1
+/// pc 1: acc +1, acc 1
+///      0: nop +0
+///   => 1: acc +1
+///      2: jmp -1
+/// pc 2: jmp -1, acc 1
+/// "
+/// );
+/// ```
+pub struct Debugger<'a> {
+    program: &'a Program,
+    machine: Machine,
+    history: Vec<Machine>,
+    breakpoints: Vec<Breakpoint>,
+    watch_acc: bool,
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(program: &'a Program) -> Debugger<'a> {
+        Debugger {
+            program,
+            machine: Machine::new(),
+            history: vec![],
+            breakpoints: vec![],
+            watch_acc: false,
+        }
+    }
+
+    /// The current state of the machine.
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    /// Run commands from `input` until it ends or a `quit` command,
+    /// writing the responses to `out`.
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, mut out: W) -> io::Result<()> {
+        for line in input.lines() {
+            if !self.execute(line?.trim(), &mut out)? {
+                break;
+            }
+        }
+        out.flush()
+    }
+
+    /// Run a single command, returning `false` if it was `quit`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::vm::{debug::Debugger, Program};
+    ///
+    /// let program: Program = "acc +2\nnop +0\nacc -1".parse().unwrap();
+    /// let mut debugger = Debugger::new(&program);
+    /// let mut out = vec![];
+    /// for command in &["watch acc", "c", "c", "c", "back 9"] {
+    ///     assert!(debugger.execute(command, &mut out).unwrap());
+    /// }
+    /// assert!(!debugger.execute("quit", &mut out).unwrap());
+    /// assert_eq!(
+    ///     String::from_utf8(out).unwrap(),
+    ///     "watching acc
+    /// acc changed from 0 to 2
+    /// pc 1: nop +0, acc 2
+    /// acc changed from 2 to 1
+    /// pc 3: end of program, acc 1
+    /// terminated with acc 1
+    /// cannot go back 9 steps, only 3 have been taken
+    /// "
+    /// );
+    /// assert_eq!(debugger.machine().acc(), 1);
+    /// ```
+    pub fn execute<W: Write>(&mut self, command: &str, out: &mut W) -> io::Result<bool> {
+        let mut words = command.split_whitespace();
+        let name = match words.next() {
+            Some(name) => name,
+            None => return Ok(true),
+        };
+        let argument = words.next();
+        match (name, argument) {
+            ("step", n) | ("s", n) => match count(n) {
+                Ok(n) => self.step(n, out)?,
+                Err(e) => writeln!(out, "{}", e)?,
+            },
+            ("continue", None) | ("c", None) => self.resume(out)?,
+            ("break", Some(arg)) | ("b", Some(arg)) => match self.breakpoint(arg) {
+                Ok(b) => {
+                    writeln!(out, "breakpoint set at {}", b)?;
+                    if !self.breakpoints.contains(&b) {
+                        self.breakpoints.push(b);
+                    }
+                }
+                Err(e) => writeln!(out, "{}", e)?,
+            },
+            ("delete", Some(arg)) | ("d", Some(arg)) => match self.breakpoint(arg) {
+                Ok(b) if self.breakpoints.contains(&b) => {
+                    self.breakpoints.retain(|other| *other != b);
+                    writeln!(out, "breakpoint at {} deleted", b)?;
+                }
+                Ok(b) => writeln!(out, "there is no breakpoint at {}", b)?,
+                Err(e) => writeln!(out, "{}", e)?,
+            },
+            ("watch", Some("acc")) | ("w", Some("acc")) => {
+                self.watch_acc = true;
+                writeln!(out, "watching acc")?;
+            }
+            ("unwatch", Some("acc")) => {
+                self.watch_acc = false;
+                writeln!(out, "stopped watching acc")?;
+            }
+            ("print", None) | ("p", None) => self.print(out)?,
+            ("back", n) => match count(n) {
+                Ok(n) => self.back(n, out)?,
+                Err(e) => writeln!(out, "{}", e)?,
+            },
+            ("help", None) | ("h", None) => writeln!(out, "{}", HELP)?,
+            ("quit", None) | ("q", None) => return Ok(false),
+            _ => writeln!(out, "unknown command {}, try help", command)?,
+        }
+        Ok(true)
+    }
+
+    /// Run one instruction, returning whether the machine can keep going.
+    fn step_once<W: Write>(&mut self, out: &mut W) -> io::Result<bool> {
+        if self.machine.pc() == self.program.len() {
+            writeln!(out, "terminated with acc {}", self.machine.acc())?;
+            return Ok(false);
+        }
+        let before = self.machine.clone();
+        match self.machine.step(self.program) {
+            Ok(()) => {
+                self.history.push(before);
+                Ok(true)
+            }
+            Err(trap) => {
+                writeln!(out, "trap: {}", trap)?;
+                Ok(false)
+            }
+        }
+    }
+
+    fn step<W: Write>(&mut self, n: usize, out: &mut W) -> io::Result<()> {
+        for _ in 0..n {
+            if !self.step_once(out)? {
+                return Ok(());
+            }
+        }
+        self.status(out)
+    }
+
+    /// Keep stepping until something worth stopping for happens.
+    fn resume<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        let mut visited = vec![false; self.program.len()];
+        loop {
+            let pc = self.machine.pc();
+            if let Some(seen) = visited.get_mut(pc) {
+                if *seen {
+                    writeln!(out, "stopped before running pc {} a second time", pc)?;
+                    return self.status(out);
+                }
+                *seen = true;
+            }
+            let acc = self.machine.acc();
+            if !self.step_once(out)? {
+                return Ok(());
+            }
+            if self.watch_acc && self.machine.acc() != acc {
+                writeln!(out, "acc changed from {} to {}", acc, self.machine.acc())?;
+                return self.status(out);
+            }
+            if let Some(b) = self.breakpoint_at(self.machine.pc()) {
+                writeln!(out, "stopped at breakpoint {}", b)?;
+                return self.status(out);
+            }
+        }
+    }
+
+    fn back<W: Write>(&mut self, n: usize, out: &mut W) -> io::Result<()> {
+        if n > self.history.len() {
+            writeln!(
+                out,
+                "cannot go back {} steps, only {} have been taken",
+                n,
+                self.history.len()
+            )?;
+            return Ok(());
+        }
+        let keep = self.history.len() - n;
+        if let Some(earlier) = self.history.drain(keep..).next() {
+            self.machine = earlier;
+        }
+        self.status(out)
+    }
+
+    /// The breakpoint stopping the machine before running the instruction
+    /// at `pc`, if any.
+    fn breakpoint_at(&self, pc: usize) -> Option<&Breakpoint> {
+        let instruction = self.program.get(pc)?;
+        self.breakpoints.iter().find(|b| match b {
+            Breakpoint::Pc(p) => *p == pc,
+            Breakpoint::Opcode(opcode) => opcode == instruction.opcode(),
+        })
+    }
+
+    fn breakpoint(&self, arg: &str) -> Result<Breakpoint, String> {
+        if let Ok(pc) = arg.parse() {
+            if pc >= self.program.len() {
+                return Err(format!(
+                    "pc {} is outside of the program, which has {} instructions",
+                    pc,
+                    self.program.len()
+                ));
+            }
+            return Ok(Breakpoint::Pc(pc));
+        }
+        match arg {
+            "acc" | "jmp" | "nop" => Ok(Breakpoint::Opcode(arg.to_string())),
+            _ => Err(format!("expected a pc or an opcode, but found {}", arg)),
+        }
+    }
+
+    fn status<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let pc = self.machine.pc();
+        match self.program.get(pc) {
+            Some(instruction) => writeln!(
+                out,
+                "pc {}: {}, acc {}",
+                pc,
+                instruction,
+                self.machine.acc()
+            ),
+            None => writeln!(out, "pc {}: end of program, acc {}", pc, self.machine.acc()),
+        }
+    }
+
+    fn print<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        self.status(out)?;
+        let pc = self.machine.pc();
+        let first = pc.saturating_sub(CONTEXT);
+        let last = (pc + CONTEXT + 1).min(self.program.len());
+        let width = self.program.len().to_string().len();
+        for i in first..last {
+            let marker = if i == pc { "=>" } else { "  " };
+            writeln!(
+                out,
+                "  {} {:>width$}: {}",
+                marker,
+                i,
+                self.program[i],
+                width = width
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse the optional count given to `step` and `back`.
+fn count(arg: Option<&str>) -> Result<usize, String> {
+    match arg {
+        None => Ok(1),
+        Some(n) => n
+            .parse()
+            .map_err(|_| format!("expected a number of steps, but found {}", n)),
+    }
+}
@@ -0,0 +1,123 @@
+use std::{collections::HashSet, fmt::Write};
+
+use super::{
+    analysis::{self, Cfg},
+    Instruction, Program,
+};
+
+/// Draw the control flow graph of a program in Graphviz DOT.
+///
+/// Every basic block is a node listing its instructions. `jmp` edges are
+/// solid and fall-through edges dashed. The blocks and edges run when
+/// starting the program are blue, except for the ones forming the loop it
+/// gets stuck in, which are red. If flipping a single `jmp` or `nop` makes
+/// the program end, its block is outlined in orange and the instruction
+/// is marked.
+///
+/// # Examples
+///
+/// ```
+/// use utils::vm::{dot, Program};
+///
+/// let p: Program = "nop +0\nacc +1\njmp -1".parse().unwrap();
+/// assert_eq!(
+///     dot::cfg(&p),
+///     r#"digraph program {
+///     node [shape=box, fontname="monospace"];
+///     b0 [label="0: nop +0\l", style=filled, fillcolor=lightblue];
+///     b1 [label="1: acc +1\l2: jmp -1  <- flip to nop -1\l", style=filled, fillcolor=salmon, color=orange, penwidth=3];
+///     end [shape=doublecircle];
+///     b0 -> b1 [style=dashed, color=blue, penwidth=2];
+///     b1 -> b1 [color=red, penwidth=2];
+/// }
+/// "#
+/// );
+/// ```
+pub fn cfg(program: &Program) -> String {
+    let blocks = analysis::basic_blocks(program);
+    let graph = Cfg::new(program);
+    let path = graph.executed_path();
+    // The path ends by looping back to an instruction it already ran, by
+    // ending the program or by jumping outside of it.
+    let loop_start = path
+        .last()
+        .and_then(|last| graph.successor(*last))
+        .and_then(|pc| path.iter().position(|p| *p == pc));
+    let executed: HashSet<usize> = path.iter().copied().collect();
+    let looping: HashSet<usize> = match loop_start {
+        Some(i) => path[i..].iter().copied().collect(),
+        None => HashSet::new(),
+    };
+    let repair = analysis::repair(program);
+
+    let block_of = |pc: usize| {
+        if pc == program.len() {
+            "end".to_string()
+        } else {
+            let i = blocks.iter().position(|b| b.contains(&pc)).unwrap_or(0);
+            format!("b{}", blocks[i].start)
+        }
+    };
+
+    let mut out = String::new();
+    // Writing to a string cannot fail.
+    let _ = writeln!(out, "digraph program {{");
+    let _ = writeln!(out, "    node [shape=box, fontname=\"monospace\"];");
+    for block in blocks.iter() {
+        let mut label = String::new();
+        for pc in block.clone() {
+            let _ = write!(label, "{}: {}", pc, program[pc]);
+            if let Some(r) = repair.filter(|r| r.pc == pc) {
+                let _ = write!(label, "  <- flip to {}", r.instruction);
+            }
+            label.push_str("\\l");
+        }
+        let mut attributes = vec![format!("label=\"{}\"", label)];
+        if looping.contains(&block.start) {
+            attributes.push("style=filled, fillcolor=salmon".to_string());
+        } else if executed.contains(&block.start) {
+            attributes.push("style=filled, fillcolor=lightblue".to_string());
+        }
+        if repair.map(|r| block.contains(&r.pc)).unwrap_or(false) {
+            attributes.push("color=orange, penwidth=3".to_string());
+        }
+        let _ = writeln!(out, "    b{} [{}];", block.start, attributes.join(", "));
+    }
+    let _ = writeln!(out, "    end [shape=doublecircle];");
+    let mut trapped = false;
+    for block in blocks.iter() {
+        let last = block.end - 1;
+        let jumps = matches!(program[last], Instruction::Jmp(_));
+        let target = match graph.successor(last) {
+            Some(next) => block_of(next),
+            None => {
+                trapped = true;
+                "trap".to_string()
+            }
+        };
+        let mut attributes = vec![];
+        if !jumps {
+            attributes.push("style=dashed".to_string());
+        }
+        let closes_loop = graph
+            .successor(last)
+            .map(|next| looping.contains(&last) && looping.contains(&next))
+            .unwrap_or(false);
+        if closes_loop {
+            attributes.push("color=red, penwidth=2".to_string());
+        } else if executed.contains(&last) {
+            attributes.push("color=blue, penwidth=2".to_string());
+        }
+        let attributes = if attributes.is_empty() {
+            String::new()
+        } else {
+            format!(" [{}]", attributes.join(", "))
+        };
+        let _ = writeln!(out, "    b{} -> {}{};", block.start, target, attributes);
+    }
+    if trapped {
+        let _ = writeln!(out, "    trap [shape=octagon, color=red];");
+    }
+    let _ = writeln!(out, "}}");
+    out
+}
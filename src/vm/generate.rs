@@ -0,0 +1,95 @@
+use super::{Instruction, Program};
+use crate::random::Rng;
+
+/// The largest operand given to generated `acc` instructions, small
+/// enough for the accumulator never to overflow.
+const MAX_ACC: i64 = 50;
+
+/// How a generated program behaves when run from its first instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ending {
+    /// The program runs past its last instruction.
+    Terminates,
+    /// The program comes back to an instruction it has already run.
+    Loops,
+}
+
+/// Generate a random program of `size` instructions that ends the given
+/// way.
+///
+/// The program runs a random selection of its instructions in a random
+/// order, linking them with jumps, before running past the end or
+/// jumping back to one of them. The instructions it never runs are
+/// random too. Every jump stays within the program, and no program runs
+/// long enough to overflow the accumulator.
+///
+/// # Panics
+///
+/// Panics if asked for an empty program that loops.
+///
+/// # Examples
+///
+/// ```
+/// use utils::random::Rng;
+/// use utils::vm::{generate::{self, Ending}, Machine, RunOutcome};
+///
+/// let mut rng = Rng::new(1);
+/// let p = generate::program(&mut rng, 20, Ending::Terminates);
+/// assert_eq!(p.len(), 20);
+/// assert!(matches!(Machine::new().run(&p), RunOutcome::Terminated { .. }));
+///
+/// let p = generate::program(&mut rng, 20, Ending::Loops);
+/// assert!(matches!(Machine::new().run(&p), RunOutcome::InfiniteLoop { .. }));
+///
+/// // The same seed generates the same program.
+/// let again = generate::program(&mut Rng::new(1), 20, Ending::Terminates);
+/// assert_eq!(again, generate::program(&mut Rng::new(1), 20, Ending::Terminates));
+/// ```
+pub fn program(rng: &mut Rng, size: usize, ending: Ending) -> Program {
+    assert!(
+        size > 0 || ending == Ending::Terminates,
+        "an empty program cannot loop"
+    );
+    let mut instructions: Vec<Instruction> =
+        (0..size).map(|pc| instruction(rng, pc, size)).collect();
+    if size == 0 {
+        return Program::new(instructions);
+    }
+
+    let mut rest: Vec<usize> = (1..size).collect();
+    rng.shuffle(&mut rest);
+    let mut path = vec![0];
+    path.extend_from_slice(&rest[..rng.below(size)]);
+    for (i, pc) in path.iter().enumerate() {
+        let next = match (path.get(i + 1), ending) {
+            (Some(next), _) => *next,
+            (None, Ending::Terminates) => size,
+            (None, Ending::Loops) => path[rng.below(i + 1)],
+        };
+        instructions[*pc] = if next == pc + 1 {
+            match rng.below(3) {
+                0 => Instruction::Acc(rng.range(-MAX_ACC..=MAX_ACC) as i32),
+                1 => Instruction::Nop(random_offset(rng, *pc, size)),
+                _ => Instruction::Jmp(1),
+            }
+        } else {
+            Instruction::Jmp(next as i32 - *pc as i32)
+        };
+    }
+    Program::new(instructions)
+}
+
+/// Any instruction, jumping to somewhere within the program.
+fn instruction(rng: &mut Rng, pc: usize, size: usize) -> Instruction {
+    match rng.below(3) {
+        0 => Instruction::Acc(rng.range(-MAX_ACC..=MAX_ACC) as i32),
+        1 => Instruction::Jmp(random_offset(rng, pc, size)),
+        _ => Instruction::Nop(random_offset(rng, pc, size)),
+    }
+}
+
+/// The offset from `pc` to a random instruction or the end of the
+/// program.
+fn random_offset(rng: &mut Rng, pc: usize, size: usize) -> i32 {
+    rng.below(size + 1) as i32 - pc as i32
+}
@@ -0,0 +1,619 @@
+use std::{collections::BTreeMap, convert::TryFrom, fmt, str::FromStr};
+
+use super::{parse_operand, Executable, Instruction, TrapKind};
+use crate::parse::{self, ParseError, Span};
+
+/// An operand of an instruction: a number or the name of a register.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Operand {
+    Number(i64),
+    Register(String),
+}
+
+/// Parse an operand, which is either a number starting with its sign or
+/// a name like `acc`.
+///
+/// # Examples
+///
+/// ```
+/// use utils::vm::isa::Operand;
+///
+/// assert_eq!("-4".parse(), Ok(Operand::Number(-4)));
+/// assert_eq!("acc".parse(), Ok(Operand::Register("acc".to_string())));
+/// assert!("4".parse::<Operand>().is_err());
+/// ```
+impl FromStr for Operand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            if s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                Ok(Operand::Register(s.to_string()))
+            } else {
+                Err(format!("{:?} is not a valid register name", s))
+            }
+        } else {
+            parse_operand(s).map(Operand::Number)
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Number(n) => write!(f, "{:+}", n),
+            Operand::Register(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// An instruction the console can be taught to run.
+pub trait Opcode: Send + Sync {
+    /// The name the instruction is written with, like `acc`.
+    fn name(&self) -> &str;
+
+    /// The number of operands the instruction takes.
+    fn arity(&self) -> usize {
+        1
+    }
+
+    /// The registers the instruction uses, which are added to every
+    /// instruction set it is registered in.
+    fn registers(&self) -> &[&str] {
+        &[]
+    }
+
+    /// Parse the operand at `index`, rejecting the ones the instruction
+    /// cannot use.
+    fn parse(&self, index: usize, operand: &str) -> Result<Operand, String> {
+        let _ = index;
+        operand.parse()
+    }
+
+    /// Whether where the machine goes after the instruction can depend on
+    /// the registers, like for a jump taken only if a register is not 0.
+    /// Instructions that do must say so.
+    ///
+    /// A machine running a program without such instructions knows it is
+    /// stuck as soon as it comes back to an instruction. Otherwise it has
+    /// to come back to it with the same registers.
+    fn branches_on_registers(&self) -> bool {
+        false
+    }
+
+    /// The instruction as one of the handheld game console's own, if it
+    /// is one.
+    fn instruction(&self, operands: &[Operand]) -> Option<Instruction> {
+        let _ = operands;
+        None
+    }
+
+    /// Run the instruction. Unless it calls [`State::jump`], the machine
+    /// goes on to the next instruction.
+    fn execute(&self, operands: &[Operand], state: &mut State) -> Result<(), TrapKind>;
+}
+
+/// An instruction of the handheld game console, taking a single number
+/// and running exactly like the [`Instruction`] of the same name.
+#[derive(Clone, Copy, Debug)]
+pub struct Console(fn(i32) -> Instruction);
+
+impl Console {
+    pub const ACC: Console = Console(Instruction::Acc);
+    pub const JMP: Console = Console(Instruction::Jmp);
+    pub const NOP: Console = Console(Instruction::Nop);
+}
+
+impl Opcode for Console {
+    fn name(&self) -> &str {
+        (self.0)(0).opcode()
+    }
+
+    fn registers(&self) -> &[&str] {
+        match (self.0)(0) {
+            Instruction::Acc(_) => &["acc"],
+            _ => &[],
+        }
+    }
+
+    fn parse(&self, _: usize, operand: &str) -> Result<Operand, String> {
+        parse_operand::<i32>(operand).map(|n| Operand::Number(n.into()))
+    }
+
+    fn instruction(&self, operands: &[Operand]) -> Option<Instruction> {
+        match operands {
+            [Operand::Number(n)] => i32::try_from(*n).ok().map(self.0),
+            _ => None,
+        }
+    }
+
+    fn execute(&self, operands: &[Operand], state: &mut State) -> Result<(), TrapKind> {
+        self.instruction(operands)
+            .expect("console instructions are parsed with a single number")
+            .execute(state)
+    }
+}
+
+/// The instructions and registers of a console.
+///
+/// The default instruction set is the one of the handheld game console:
+/// `acc`, `jmp` and `nop`, with a single register, `acc`. Other
+/// instructions are added by implementing [`Opcode`] and registering
+/// them.
+///
+/// # Examples
+///
+/// ```
+/// use utils::vm::isa::{InstructionSet, Opcode, Operand, State};
+/// use utils::vm::{Machine, RunOutcome, TrapKind};
+///
+/// /// Add the second operand to the register named by the first one.
+/// struct Add;
+///
+/// impl Opcode for Add {
+///     fn name(&self) -> &str {
+///         "add"
+///     }
+///
+///     fn arity(&self) -> usize {
+///         2
+///     }
+///
+///     fn parse(&self, index: usize, operand: &str) -> Result<Operand, String> {
+///         match operand.parse()? {
+///             Operand::Number(_) if index == 0 => Err("add writes to a register".to_string()),
+///             operand => Ok(operand),
+///         }
+///     }
+///
+///     fn execute(&self, operands: &[Operand], state: &mut State) -> Result<(), TrapKind> {
+///         if let Operand::Register(name) = &operands[0] {
+///             let sum = state.register(name).checked_add(state.value(&operands[1]));
+///             state.set(name, sum.ok_or(TrapKind::Overflow)?);
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// let set = InstructionSet::default().with_register("b").register(Add);
+/// let code = set.parse("add b +20\nadd acc b\nacc +1\njmp +1").unwrap();
+/// assert_eq!(code.to_string(), "add b +20\nadd acc b\nacc +1\njmp +1\n");
+/// let mut machine = Machine::for_set(&set);
+/// assert_eq!(machine.run(&code), RunOutcome::Terminated { acc: 21 });
+/// assert_eq!(machine.state().register("b"), 20);
+///
+/// // Traced machines record these instructions as well.
+/// let looping = set.parse("add b +1\nadd acc b\njmp -2").unwrap();
+/// let mut machine = Machine::for_set(&set).traced();
+/// machine.run(&looping);
+/// let profile = machine.profile().unwrap();
+/// assert_eq!(profile.counts(), &[1, 1, 1]);
+/// assert_eq!(profile.first_revisit().map(|r| (r.step, r.pc)), Some((3, 0)));
+/// let step = &profile.trace()[1];
+/// assert_eq!((step.instruction.as_str(), step.acc_before, step.acc_after), ("add acc b", 0, 1));
+///
+/// // Unknown opcodes and registers are caught before running anything.
+/// let err = set.parse("acc +1\nmul acc +2").unwrap_err();
+/// assert_eq!(err.message, "unknown opcode mul, expected acc, jmp, nop or add");
+/// let err = set.parse("add c +1").unwrap_err();
+/// assert_eq!(err.message, "unknown register c, expected acc or b");
+/// let err = set.parse("add +1 +1").unwrap_err();
+/// assert_eq!(err.message, "add writes to a register");
+/// assert!(InstructionSet::default().parse("add acc +1").is_err());
+/// ```
+///
+/// A machine running instructions that jump depending on the registers
+/// only gives up on a program once it comes back to an instruction with
+/// the same registers:
+///
+/// ```
+/// use utils::vm::isa::{InstructionSet, Opcode, Operand, State};
+/// use utils::vm::{Machine, RunOutcome, TrapKind};
+///
+/// /// Take 1 from a register.
+/// struct Dec;
+///
+/// impl Opcode for Dec {
+///     fn name(&self) -> &str {
+///         "dec"
+///     }
+///
+///     fn execute(&self, operands: &[Operand], state: &mut State) -> Result<(), TrapKind> {
+///         if let Operand::Register(name) = &operands[0] {
+///             let value = state.register(name).checked_sub(1);
+///             state.set(name, value.ok_or(TrapKind::Overflow)?);
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// /// Jump by the second operand if the first one is not 0.
+/// struct Jnz;
+///
+/// impl Opcode for Jnz {
+///     fn name(&self) -> &str {
+///         "jnz"
+///     }
+///
+///     fn arity(&self) -> usize {
+///         2
+///     }
+///
+///     fn branches_on_registers(&self) -> bool {
+///         true
+///     }
+///
+///     fn execute(&self, operands: &[Operand], state: &mut State) -> Result<(), TrapKind> {
+///         if state.value(&operands[0]) != 0 {
+///             state.jump(state.value(&operands[1]))?;
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// let set = InstructionSet::default().register(Dec).register(Jnz);
+/// let countdown = set.parse("acc +3\njnz acc +2\njmp +3\ndec acc\njmp -3").unwrap();
+/// assert_eq!(Machine::for_set(&set).run(&countdown), RunOutcome::Terminated { acc: 0 });
+///
+/// let stuck = set.parse("acc +3\njnz acc +0").unwrap();
+/// assert_eq!(
+///     Machine::for_set(&set).run(&stuck),
+///     RunOutcome::InfiniteLoop { acc: 3, pc: 1, loop_start: 1 }
+/// );
+/// ```
+pub struct InstructionSet {
+    opcodes: Vec<Box<dyn Opcode>>,
+    registers: Vec<String>,
+}
+
+impl InstructionSet {
+    /// An instruction set without any instructions or registers.
+    pub fn empty() -> InstructionSet {
+        InstructionSet {
+            opcodes: vec![],
+            registers: vec![],
+        }
+    }
+
+    /// Add an instruction, replacing any other one with the same name.
+    pub fn register<O: Opcode + 'static>(mut self, opcode: O) -> InstructionSet {
+        for name in opcode.registers() {
+            self = self.with_register(name);
+        }
+        self.opcodes.retain(|o| o.name() != opcode.name());
+        self.opcodes.push(Box::new(opcode));
+        self
+    }
+
+    /// Add a register, which starts at 0.
+    pub fn with_register(mut self, name: &str) -> InstructionSet {
+        if !self.registers.iter().any(|r| r == name) {
+            self.registers.push(name.to_string());
+        }
+        self
+    }
+
+    /// The names of the registers, in the order they were added.
+    pub fn registers(&self) -> &[String] {
+        &self.registers
+    }
+
+    /// The instruction called `name`, if it has been registered.
+    pub fn opcode(&self, name: &str) -> Option<&dyn Opcode> {
+        self.opcodes
+            .iter()
+            .find(|o| o.name() == name)
+            .map(|o| o.as_ref())
+    }
+
+    /// Parse a program with one instruction per line, using only the
+    /// instructions and registers of this set.
+    pub fn parse(&self, source: &str) -> Result<Code<'_>, ParseError> {
+        let ops = parse::lines(source, |line| self.parse_op(line))?;
+        Ok(Code { set: self, ops })
+    }
+
+    /// Parse a single instruction of the handheld game console.
+    pub(super) fn parse_instruction(&self, line: &str) -> Result<Instruction, ParseError> {
+        let op = self.parse_op(line)?;
+        let opcode = &self.opcodes[op.opcode];
+        opcode.instruction(&op.operands).ok_or_else(|| {
+            ParseError::new(
+                Span::within(line, opcode.name()),
+                format!("{} is not an instruction of the console", opcode.name()),
+            )
+        })
+    }
+
+    fn parse_op(&self, line: &str) -> Result<Op, ParseError> {
+        let mut words = line.split(' ');
+        let name = words.next().unwrap_or("");
+        if name.is_empty() {
+            return Err(ParseError::new(
+                Span::new(1, 1, 1),
+                "expected an instruction",
+            ));
+        }
+        let opcode = self
+            .opcodes
+            .iter()
+            .position(|o| o.name() == name)
+            .ok_or_else(|| {
+                let names: Vec<&str> = self.opcodes.iter().map(|o| o.name()).collect();
+                ParseError::new(
+                    Span::within(line, name),
+                    format!("unknown opcode {}, expected {}", name, either(&names)),
+                )
+            })?;
+        let arity = self.opcodes[opcode].arity();
+        let words: Vec<&str> = words.collect();
+        if words.len() < arity {
+            return Err(ParseError::new(
+                Span::end_of(line),
+                format!(
+                    "{} takes {}, but found {}",
+                    name,
+                    operands(arity),
+                    words.len()
+                ),
+            ));
+        }
+        if let Some(extra) = words.get(arity) {
+            return Err(ParseError::new(
+                Span::within(line, extra),
+                format!("{} takes {}", name, operands(arity)),
+            ));
+        }
+        let operands = words
+            .iter()
+            .enumerate()
+            .map(|(i, word)| {
+                let at = Span::within(line, word);
+                let operand = self.opcodes[opcode]
+                    .parse(i, word)
+                    .map_err(|e| ParseError::new(at, e))?;
+                match &operand {
+                    Operand::Register(r) if !self.registers.contains(r) => {
+                        let names: Vec<&str> = self.registers.iter().map(|r| r.as_str()).collect();
+                        Err(ParseError::new(
+                            at,
+                            format!("unknown register {}, expected {}", r, either(&names)),
+                        ))
+                    }
+                    _ => Ok(operand),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Op { opcode, operands })
+    }
+}
+
+impl fmt::Debug for InstructionSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<&str> = self.opcodes.iter().map(|o| o.name()).collect();
+        f.debug_struct("InstructionSet")
+            .field("opcodes", &names)
+            .field("registers", &self.registers)
+            .finish()
+    }
+}
+
+impl Default for InstructionSet {
+    fn default() -> InstructionSet {
+        InstructionSet::empty()
+            .register(Console::ACC)
+            .register(Console::JMP)
+            .register(Console::NOP)
+    }
+}
+
+/// List names like `a, b or c`.
+fn either(names: &[&str]) -> String {
+    match names.split_last() {
+        None => "nothing".to_string(),
+        Some((last, [])) => last.to_string(),
+        Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+    }
+}
+
+fn operands(n: usize) -> String {
+    match n {
+        1 => "1 operand".to_string(),
+        _ => format!("{} operands", n),
+    }
+}
+
+/// A parsed instruction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Op {
+    /// The index of the opcode in the instruction set.
+    opcode: usize,
+    operands: Vec<Operand>,
+}
+
+/// A program parsed with an [`InstructionSet`].
+#[derive(Debug)]
+pub struct Code<'a> {
+    set: &'a InstructionSet,
+    ops: Vec<Op>,
+}
+
+impl<'a> Code<'a> {
+    /// The instruction set the program was parsed with.
+    pub fn set(&self) -> &'a InstructionSet {
+        self.set
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// The instruction at `pc`.
+    pub fn opcode(&self, pc: usize) -> &'a dyn Opcode {
+        self.set.opcodes[self.ops[pc].opcode].as_ref()
+    }
+
+    /// The operands of the instruction at `pc`.
+    pub fn operands(&self, pc: usize) -> &[Operand] {
+        &self.ops[pc].operands
+    }
+}
+
+impl Executable for Code<'_> {
+    fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    fn execute(&self, pc: usize, state: &mut State) -> Result<(), TrapKind> {
+        self.opcode(pc).execute(self.operands(pc), state)
+    }
+
+    fn render(&self, pc: usize) -> String {
+        let mut line = self.opcode(pc).name().to_string();
+        for operand in self.operands(pc) {
+            line.push(' ');
+            line.push_str(&operand.to_string());
+        }
+        line
+    }
+
+    fn branches_on_registers(&self) -> bool {
+        self.ops
+            .iter()
+            .any(|op| self.set.opcodes[op.opcode].branches_on_registers())
+    }
+}
+
+impl fmt::Display for Code<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for pc in 0..self.len() {
+            writeln!(f, "{}", self.render(pc))?;
+        }
+        Ok(())
+    }
+}
+
+/// The registers of a console and the instruction it is running, as seen
+/// by [`Opcode::execute`].
+///
+/// The accumulator is always there, and kept apart from the other
+/// registers so that the console's own instructions do not have to look
+/// it up by name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct State {
+    pub(super) pc: usize,
+    /// Where to go after the current instruction.
+    pub(super) next: i64,
+    pub(super) acc: i64,
+    /// The registers other than the accumulator.
+    pub(super) registers: BTreeMap<String, i64>,
+}
+
+impl State {
+    /// A state at the first instruction, with every register at 0.
+    pub(super) fn new<S: AsRef<str>>(registers: &[S]) -> State {
+        State {
+            pc: 0,
+            next: 1,
+            acc: 0,
+            registers: registers
+                .iter()
+                .map(|r| r.as_ref())
+                .filter(|r| *r != "acc")
+                .map(|r| (r.to_string(), 0))
+                .collect(),
+        }
+    }
+
+    /// The index of the instruction being run.
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn acc(&self) -> i64 {
+        self.acc
+    }
+
+    pub fn set_acc(&mut self, value: i64) {
+        self.acc = value;
+    }
+
+    /// The value of a register, which is 0 for registers that do not
+    /// exist.
+    pub fn register(&self, name: &str) -> i64 {
+        if name == "acc" {
+            return self.acc;
+        }
+        self.registers.get(name).copied().unwrap_or(0)
+    }
+
+    pub fn set(&mut self, name: &str, value: i64) {
+        if name == "acc" {
+            self.acc = value;
+            return;
+        }
+        match self.registers.get_mut(name) {
+            Some(register) => *register = value,
+            None => {
+                self.registers.insert(name.to_string(), value);
+            }
+        }
+    }
+
+    /// Every register with its value, by name, starting with the
+    /// accumulator.
+    pub fn registers(&self) -> impl Iterator<Item = (&str, i64)> {
+        let others = self.registers.iter().map(|(name, v)| (name.as_str(), *v));
+        Some(("acc", self.acc)).into_iter().chain(others)
+    }
+
+    /// The value of a number, or the value of the register it names.
+    pub fn value(&self, operand: &Operand) -> i64 {
+        match operand {
+            Operand::Number(n) => *n,
+            Operand::Register(name) => self.register(name),
+        }
+    }
+
+    /// Continue at the instruction `offset` away from the current one
+    /// instead of the next one.
+    ///
+    /// Traps if the target is too far away to be represented at all,
+    /// reporting the closest target that can.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::vm::isa::{InstructionSet, Opcode, Operand, State};
+    /// use utils::vm::{Machine, RunOutcome, TrapKind};
+    ///
+    /// /// Jump by any number, not only the ones fitting in an `i32`.
+    /// struct Far;
+    ///
+    /// impl Opcode for Far {
+    ///     fn name(&self) -> &str {
+    ///         "far"
+    ///     }
+    ///
+    ///     fn execute(&self, operands: &[Operand], state: &mut State) -> Result<(), TrapKind> {
+    ///         state.jump(state.value(&operands[0]))
+    ///     }
+    /// }
+    ///
+    /// let set = InstructionSet::default().register(Far);
+    /// let code = set.parse("nop +0\nfar +9223372036854775807").unwrap();
+    /// let kind = TrapKind::JumpOutOfBounds { target: i64::MAX };
+    /// assert_eq!(Machine::for_set(&set).run(&code), RunOutcome::Trap { kind, pc: 1 });
+    /// ```
+    pub fn jump(&mut self, offset: i64) -> Result<(), TrapKind> {
+        let pc = self.pc as i64;
+        self.next = pc.checked_add(offset).ok_or(TrapKind::JumpOutOfBounds {
+            target: pc.saturating_add(offset),
+        })?;
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+use std::{fmt, sync::Arc, thread};
+
+use super::{Instruction, Machine, Program, RunOutcome};
+
+/// A way of changing a single instruction of a program.
+#[derive(Clone, Copy, Debug)]
+pub enum Strategy {
+    /// Turn an instruction made by either constructor into one made by
+    /// the other, keeping the operand, like
+    /// `Swap(Instruction::Jmp, Instruction::Nop)`.
+    Swap(fn(i32) -> Instruction, fn(i32) -> Instruction),
+    /// Flip the sign of the operand.
+    NegateOperand,
+    /// Remove the instruction, moving the ones after it up.
+    Delete,
+}
+
+impl Strategy {
+    /// The mutations of the instruction at `pc` this strategy allows.
+    fn mutations(self, pc: usize, instruction: Instruction) -> Vec<Mutation> {
+        let replace = |instruction| Mutation {
+            pc,
+            edit: Edit::Replace(instruction),
+        };
+        match self {
+            Strategy::Swap(a, b) => {
+                let operand = instruction.operand();
+                let swapped = if a(operand) == instruction {
+                    b(operand)
+                } else if b(operand) == instruction {
+                    a(operand)
+                } else {
+                    return vec![];
+                };
+                Some(swapped)
+                    .filter(|i| *i != instruction)
+                    .map(replace)
+                    .into_iter()
+                    .collect()
+            }
+            Strategy::NegateOperand => instruction
+                .operand()
+                .checked_neg()
+                .filter(|n| *n != 0)
+                .and_then(|n| Instruction::new(instruction.opcode(), n))
+                .map(replace)
+                .into_iter()
+                .collect(),
+            Strategy::Delete => vec![Mutation {
+                pc,
+                edit: Edit::Delete,
+            }],
+        }
+    }
+}
+
+/// What happens to the mutated instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edit {
+    Replace(Instruction),
+    Delete,
+}
+
+/// A change to a single instruction of a program.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Mutation {
+    pub pc: usize,
+    pub edit: Edit,
+}
+
+impl Mutation {
+    /// The program with the mutation applied.
+    pub fn apply(&self, program: &Program) -> Program {
+        let mut instructions = program.instructions().to_vec();
+        match self.edit {
+            Edit::Replace(instruction) => instructions[self.pc] = instruction,
+            Edit::Delete => {
+                instructions.remove(self.pc);
+            }
+        }
+        Program::new(instructions)
+    }
+}
+
+impl fmt::Display for Mutation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.edit {
+            Edit::Replace(instruction) => write!(f, "replace {} with {}", self.pc, instruction),
+            Edit::Delete => write!(f, "delete {}", self.pc),
+        }
+    }
+}
+
+/// Every mutation of a single instruction the strategies allow, ordered
+/// by the instruction and then by the strategy. Mutations that would
+/// leave the program unchanged are left out.
+///
+/// # Examples
+///
+/// ```
+/// use utils::vm::{mutate::{self, Strategy}, Instruction, Program};
+///
+/// let p: Program = "nop +0\nacc -1\njmp +2".parse().unwrap();
+/// let mutations = mutate::candidates(&p, &[Strategy::Swap(Instruction::Jmp, Instruction::Nop), Strategy::NegateOperand]);
+/// let described: Vec<String> = mutations.iter().map(|m| m.to_string()).collect();
+/// assert_eq!(
+///     described,
+///     vec!["replace 0 with jmp +0", "replace 1 with acc +1", "replace 2 with nop +2", "replace 2 with jmp -2"]
+/// );
+/// ```
+pub fn candidates(program: &Program, strategies: &[Strategy]) -> Vec<Mutation> {
+    program
+        .iter()
+        .enumerate()
+        .flat_map(|(pc, instruction)| {
+            strategies
+                .iter()
+                .flat_map(move |s| s.mutations(pc, *instruction))
+        })
+        .collect()
+}
+
+/// Try every mutation the strategies allow, returning the ones after
+/// which the program ends, in the order of [`candidates`], together with
+/// the accumulator at the end.
+///
+/// # Examples
+///
+/// ```
+/// use utils::vm::{mutate::{self, Edit, Mutation, Strategy}, Instruction, Program};
+///
+/// let p: Program = "nop +0\nacc +1\njmp +4\nacc +3\njmp -3\nacc -99\nacc +1\njmp -4\nacc +6"
+///     .parse()
+///     .unwrap();
+/// let swap = [Strategy::Swap(Instruction::Jmp, Instruction::Nop)];
+/// let fix = Mutation { pc: 7, edit: Edit::Replace(Instruction::Nop(-4)) };
+/// assert_eq!(mutate::search(&p, &swap), vec![(fix, 8)]);
+///
+/// let all = [Strategy::Swap(Instruction::Jmp, Instruction::Nop), Strategy::NegateOperand, Strategy::Delete];
+/// let found = mutate::search(&p, &all);
+/// assert_eq!(found, vec![(fix, 8), (Mutation { pc: 7, edit: Edit::Delete }, 8)]);
+/// assert_eq!(mutate::search_parallel(&p, &all, 4), found);
+/// ```
+pub fn search(program: &Program, strategies: &[Strategy]) -> Vec<(Mutation, i64)> {
+    terminating(program, &candidates(program, strategies))
+}
+
+/// Like [`search`], but splitting the mutations to try between `threads`
+/// threads.
+pub fn search_parallel(
+    program: &Program,
+    strategies: &[Strategy],
+    threads: usize,
+) -> Vec<(Mutation, i64)> {
+    let mutations = candidates(program, strategies);
+    if mutations.is_empty() {
+        return vec![];
+    }
+    let threads = threads.max(1);
+    let chunk = (mutations.len() + threads - 1) / threads;
+    let program = Arc::new(program.clone());
+    let mutations = Arc::new(mutations);
+    let handles: Vec<_> = (0..mutations.len())
+        .step_by(chunk)
+        .map(|start| {
+            let program = Arc::clone(&program);
+            let mutations = Arc::clone(&mutations);
+            let end = (start + chunk).min(mutations.len());
+            thread::spawn(move || terminating(&program, &mutations[start..end]))
+        })
+        .collect();
+    handles
+        .into_iter()
+        .flat_map(|h| h.join().expect("search thread panicked"))
+        .collect()
+}
+
+/// Run the program with every mutation, keeping the ones after which it
+/// ends.
+fn terminating(program: &Program, mutations: &[Mutation]) -> Vec<(Mutation, i64)> {
+    mutations
+        .iter()
+        .filter_map(|m| match Machine::new().run(&m.apply(program)) {
+            RunOutcome::Terminated { acc } => Some((*m, acc)),
+            _ => None,
+        })
+        .collect()
+}
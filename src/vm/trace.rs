@@ -0,0 +1,165 @@
+use std::fmt::Write;
+
+/// One instruction run by a machine.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Step {
+    pub pc: usize,
+    /// The instruction as written in the program.
+    pub instruction: String,
+    pub acc_before: i64,
+    pub acc_after: i64,
+}
+
+/// The first time a machine ran an instruction it had already run, or
+/// stopped because it was about to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Revisit {
+    /// The index into the trace of the second run of the instruction,
+    /// which is the length of the trace if the machine stopped before it.
+    pub step: usize,
+    pub pc: usize,
+}
+
+/// What a machine did while running a program, recorded when tracing is
+/// turned on with [`Machine::traced`](super::Machine::traced).
+///
+/// # Examples
+///
+/// ```
+/// use utils::vm::{Machine, Program};
+///
+/// let p: Program = "acc +1\njmp +1\nacc +2\njmp -2".parse().unwrap();
+/// let mut m = Machine::new().traced();
+/// m.run(&p);
+/// let profile = m.profile().unwrap();
+/// assert_eq!(profile.counts(), &[1, 1, 1, 1]);
+/// assert_eq!(profile.trace().len(), 4);
+/// // The machine stopped right before running instruction 1 again.
+/// assert_eq!(profile.first_revisit().map(|r| (r.step, r.pc)), Some((4, 1)));
+/// assert_eq!(
+///     profile.to_csv(),
+///     "step,pc,instruction,acc_before,acc_after,visit
+/// 0,0,acc +1,0,1,1
+/// 1,1,jmp +1,1,1,1
+/// 2,2,acc +2,1,3,1
+/// 3,3,jmp -2,3,3,1
+/// "
+/// );
+///
+/// // Stepping on runs it for the second time.
+/// m.step(&p).unwrap();
+/// let profile = m.profile().unwrap();
+/// assert_eq!(profile.first_revisit().map(|r| (r.step, r.pc)), Some((4, 1)));
+/// assert_eq!(profile.hottest(), Some(1));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Profile {
+    trace: Vec<Step>,
+    counts: Vec<usize>,
+    first_revisit: Option<Revisit>,
+}
+
+impl Profile {
+    pub(super) fn record(&mut self, step: Step) {
+        if step.pc >= self.counts.len() {
+            self.counts.resize(step.pc + 1, 0);
+        }
+        self.counts[step.pc] += 1;
+        if self.counts[step.pc] == 2 && self.first_revisit.is_none() {
+            self.first_revisit = Some(Revisit {
+                step: self.trace.len(),
+                pc: step.pc,
+            });
+        }
+        self.trace.push(step);
+    }
+
+    /// Note that the machine stopped before running the instruction at
+    /// `pc` again.
+    pub(super) fn revisit(&mut self, pc: usize) {
+        if self.first_revisit.is_none() {
+            self.first_revisit = Some(Revisit {
+                step: self.trace.len(),
+                pc,
+            });
+        }
+    }
+
+    /// Every instruction run, in order.
+    pub fn trace(&self) -> &[Step] {
+        &self.trace
+    }
+
+    /// How many times the instruction at each pc was run, up to the
+    /// highest pc run.
+    pub fn counts(&self) -> &[usize] {
+        &self.counts
+    }
+
+    pub fn first_revisit(&self) -> Option<Revisit> {
+        self.first_revisit
+    }
+
+    /// The pc of the instruction run the most times, the first one if
+    /// there are several.
+    pub fn hottest(&self) -> Option<usize> {
+        let max = *self.counts.iter().max()?;
+        self.counts.iter().position(|c| *c == max)
+    }
+
+    /// Write the trace as CSV with a header row, including how many
+    /// times each instruction had been run so far.
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("step,pc,instruction,acc_before,acc_after,visit\n");
+        let mut visits = vec![0; self.counts.len()];
+        for (i, step) in self.trace.iter().enumerate() {
+            visits[step.pc] += 1;
+            // Writing to a string cannot fail.
+            let _ = writeln!(
+                out,
+                "{},{},{},{},{},{}",
+                i, step.pc, step.instruction, step.acc_before, step.acc_after, visits[step.pc]
+            );
+        }
+        out
+    }
+
+    /// Write the trace, the counts and the first revisit as JSON.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use utils::vm::{Machine, Program};
+    ///
+    /// let p: Program = "nop +0\njmp -1".parse().unwrap();
+    /// let mut m = Machine::new().traced();
+    /// m.run(&p);
+    /// assert_eq!(
+    ///     m.profile().unwrap().to_json(),
+    ///     r#"{"trace":[{"pc":0,"instruction":"nop +0","acc_before":0,"acc_after":0},{"pc":1,"instruction":"jmp -1","acc_before":0,"acc_after":0}],"counts":[1,1],"first_revisit":{"step":2,"pc":0}}"#
+    /// );
+    /// ```
+    pub fn to_json(&self) -> String {
+        let steps: Vec<String> = self
+            .trace
+            .iter()
+            .map(|s| {
+                format!(
+                    r#"{{"pc":{},"instruction":"{}","acc_before":{},"acc_after":{}}}"#,
+                    s.pc, s.instruction, s.acc_before, s.acc_after
+                )
+            })
+            .collect();
+        let counts: Vec<String> = self.counts.iter().map(|c| c.to_string()).collect();
+        let revisit = match self.first_revisit {
+            Some(r) => format!(r#"{{"step":{},"pc":{}}}"#, r.step, r.pc),
+            None => "null".to_string(),
+        };
+        format!(
+            r#"{{"trace":[{}],"counts":[{}],"first_revisit":{}}}"#,
+            steps.join(","),
+            counts.join(","),
+            revisit
+        )
+    }
+}